@@ -0,0 +1,99 @@
+//! Builds TSC-like scripts out of the commands the lexer knows, and checks
+//! that extracting their dialogue and rebuilding them gives back the same
+//! bytes. Run with `cargo fuzz run round_trip`, and shrink a crash with
+//! `cargo fuzz tmin round_trip <artifact>`.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use doukutsu_extractor::{DumpOptions, TextEncoding, dump_script, tsc_decode, tsc_encode};
+use libfuzzer_sys::fuzz_target;
+use std::path::PathBuf;
+
+/// The commands the lexer has tokens for, without their arguments.
+const COMMANDS: &[&str] = &[
+    "<MSG", "<NOD", "<CLR", "<END", "<TUR", "<SAT", "<CAT", "<FMU", "<KEY", "<CLO", "<FAC", "<GIT",
+    "<WAI", "<CMU", "<NUM", "<FLJ", "<TRA", "<ANP", "<ML+", "<AM+", "<IT-", "<SOU", "<MS2", "<MS3",
+    "<PRI", "<FRE",
+];
+
+#[derive(Debug, Arbitrary)]
+enum Piece {
+    /// A command, followed by as many four-digit arguments as given.
+    Command {
+        index: u8,
+        args: Vec<u16>,
+    },
+    /// An event label like `#0200`.
+    Label(u16),
+    Text(String),
+    Digits(u16),
+    LineBreak,
+    Byte(u8),
+}
+
+#[derive(Debug, Arbitrary)]
+struct Script {
+    pieces: Vec<Piece>,
+    shift_jis: bool,
+    inline_codes: bool,
+    nod_breaks: bool,
+    escape_bytes: bool,
+    /// End lines with `\n` rather than the game's `\r\n`.
+    lf_only: bool,
+}
+
+impl Script {
+    fn bytes(&self) -> Vec<u8> {
+        let mut out = vec![];
+        for piece in &self.pieces {
+            match piece {
+                Piece::Command { index, args } => {
+                    out.extend(COMMANDS[*index as usize % COMMANDS.len()].bytes());
+                    let args: Vec<_> = args.iter().map(|a| format!("{:04}", a % 10000)).collect();
+                    out.extend(args.join(":").bytes());
+                }
+                Piece::Label(n) => out.extend(format!("#{:04}", n % 10000).bytes()),
+                Piece::Text(s) => out.extend(s.bytes()),
+                Piece::Digits(n) => out.extend(n.to_string().bytes()),
+                Piece::LineBreak if self.lf_only => out.push(b'\n'),
+                Piece::LineBreak => out.extend(b"\r\n"),
+                Piece::Byte(b) => out.push(*b),
+            }
+        }
+        out
+    }
+}
+
+fuzz_target!(|script: Script| {
+    let options = DumpOptions {
+        encoding: match script.shift_jis {
+            true => TextEncoding::ShiftJis,
+            false => TextEncoding::Utf8,
+        },
+        // failing on a file that doesn't rebuild exactly is the point
+        strict: true,
+        plaintext: true,
+        include_empty: true,
+        inline_codes: script.inline_codes,
+        nod_breaks: script.nod_breaks,
+        escape_bytes: script.escape_bytes,
+        ..Default::default()
+    };
+    let bytes = script.bytes();
+    let fd = dump_script(PathBuf::from("Fuzz.tsc"), bytes.clone(), &options)
+        .expect("the script should rebuild exactly")
+        .expect("empty scripts are kept");
+    assert_eq!(fd.reconstruct_bytes(options.encoding, None).unwrap(), bytes);
+    // as if every byte was part of a translated segment
+    let escaped = options.encoding.escape(&bytes);
+    assert_eq!(options.encoding.unescape(&escaped), bytes);
+    // whatever the middle byte, including a key of 0 that leaves the rest as
+    // it is
+    let encrypted = tsc_encode(bytes.clone(), None).unwrap();
+    assert_eq!(
+        encrypted[bytes.len() / 2..].first(),
+        bytes[bytes.len() / 2..].first()
+    );
+    assert_eq!(tsc_decode(encrypted, None).unwrap(), bytes);
+});
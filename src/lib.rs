@@ -0,0 +1,3285 @@
+//! Extracting the dialogue from Cave Story's TSC scripts and writing translations back.
+
+use anyhow::{Context, Result, anyhow, bail};
+use glob::{MatchOptions, glob_with};
+use indicatif::{ParallelProgressIterator, ProgressBar};
+use log::{debug, warn};
+use logos::Logos;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{BufWriter, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub enum LexingError {
+    #[default]
+    Other,
+}
+
+#[derive(Logos, Debug, Clone, PartialEq)]
+#[logos(skip r"\r?\n")]
+pub enum Token {
+    // #[token("\r\n")]
+    // Newline,
+    /// Any other command, along with its arguments if it's one of the
+    /// [`COMMAND_ARGUMENTS`].
+    #[regex(r"<[A-Z\+\-\d]{3}", skip_arguments, priority = 6)]
+    LbCode,
+    #[regex(r"\d{3,4}", priority = 7)]
+    NumCode,
+    #[token("<FAC0000")]
+    NormalWidth,
+    #[token("<FAC0001")]
+    SueSmile,
+    #[token("<FAC0002")]
+    SueFrown,
+    #[token("<FAC0003")]
+    SueAngry,
+    #[token("<FAC0004")]
+    SueHurt,
+    #[token("<FAC0005")]
+    BalrogNormal,
+    #[token("<FAC0006")]
+    TorokoNormal,
+    #[token("<FAC0007")]
+    King,
+    #[token("<FAC0008")]
+    TorokoAngry,
+    #[token("<FAC0009")]
+    Jack,
+    #[token("<FAC0010")]
+    Kazuma,
+    #[token("<FAC0011")]
+    TorokoRage,
+    #[token("<FAC0012")]
+    Igor,
+    #[token("<FAC0013")]
+    Jenka,
+    #[token("<FAC0014")]
+    BalrogSmile,
+    #[token("<FAC0015")]
+    MiseryNormal,
+    #[token("<FAC0016")]
+    MiserySmile,
+    #[token("<FAC0017")]
+    BoosterHurt,
+    #[token("<FAC0018")]
+    BoosterNormal,
+    #[token("<FAC0019")]
+    CurlySmile,
+    #[token("<FAC0020")]
+    CurlyFrown,
+    #[token("<FAC0021")]
+    Doctor,
+    #[token("<FAC0022")]
+    Momorin,
+    #[token("<FAC0023")]
+    BalrogHurt,
+    #[token("<FAC0024")]
+    BrokenRobot,
+    #[token("<FAC0025")]
+    CurlyUnknown,
+    #[token("<FAC0026")]
+    MiseryAngry,
+    #[token("<FAC0027")]
+    HumanSue,
+    #[token("<FAC0028")]
+    Itoh,
+    #[token("<FAC0029")]
+    Ballos,
+    /// A face added by a mod, which needs a face table to be named.
+    #[regex(r"<FAC\d{4}", priority = 3)]
+    CustomFace,
+    #[token("<MSG")]
+    Message,
+    #[token("<MS2")]
+    MessageInvisible,
+    #[token("<MS3")]
+    MessageTop,
+    #[token("<NOD")]
+    Nod,
+    #[token("<CLR")]
+    Clear,
+    #[token("<END")]
+    End,
+    #[regex(r"<GIT\d{4}")]
+    Git,
+    #[regex(r"<WAI\d{4}")]
+    Wait,
+    #[token("<TUR")]
+    Turbo,
+    /// Changes the music to the given track.
+    #[regex(r"<CMU\d{4}")]
+    Cmu,
+    /// Fades the music out.
+    #[token("<FMU")]
+    Fmu,
+    /// Raises or lowers a stat, or gives or takes an item, weapon or flag,
+    /// e.g. `<ML+0005` (more max health) or `<AM+0002:0000`, taken whole along
+    /// with its arguments so that none of them end up in the text.
+    #[regex(r"<[A-Z]{2}[\+\-]\d{4}(?::\d{4})*")]
+    StatChange,
+    /// Freezes the game, player and NPCs, until `<FRE` or the event ends.
+    #[token("<PRI")]
+    Pri,
+    /// Stops the player from moving, though NPCs carry on.
+    #[token("<KEY")]
+    Key,
+    /// Gives the player control back after `<PRI` or `<KEY`.
+    #[token("<FRE")]
+    Fre,
+    #[token("<SAT")]
+    Sat,
+    #[token("<CAT")]
+    Cat,
+    #[regex(r"<FLJ\d{4}:\d{4}")]
+    FlagJump,
+    /// Moves to another map, ending the event.
+    #[regex(r"<TRA\d{4}:\d{4}:\d{4}:\d{4}")]
+    Transport,
+    #[token("#")]
+    Pound,
+    #[token(":")]
+    Colon,
+    /// A run of text, which may contain `<NUM0000` markers for the game to
+    /// fill in a number, so that translators can move them around.
+    #[regex(r#"[\d]{3}|(?:[\-a-zA-Z.\!?=\*'" [^\x00-\x7F]]|<NUM\d{4})(?:[a-zA-Z,.!?;\d\+\-\'"= \*\r\n[^\x00-\x7F]]|<NUM\d{4})*"#, |lex| lex.slice().to_owned())]
+    Text(String),
+    #[regex(r".", priority=1, callback = |lex| lex.slice().to_owned())]
+    Other(String),
+}
+
+/// The commands that take arguments, and how many. The game reads each
+/// argument as the four characters after the command (or the previous
+/// argument and the one character separating them), whatever they are, so
+/// e.g. the `O38` of a mistyped `<SOU0O38` isn't text even though it looks
+/// like it.
+pub const COMMAND_ARGUMENTS: &[(&str, usize)] = &[
+    ("AM+", 2),
+    ("AM-", 1),
+    ("AMJ", 2),
+    ("ANP", 3),
+    ("BOA", 1),
+    ("BSL", 1),
+    ("CMP", 3),
+    ("CMU", 1),
+    ("CNP", 3),
+    ("DNA", 1),
+    ("DNP", 1),
+    ("ECJ", 2),
+    ("EQ+", 1),
+    ("EQ-", 1),
+    ("EVE", 1),
+    ("FAC", 1),
+    ("FAI", 1),
+    ("FAO", 1),
+    ("FL+", 1),
+    ("FL-", 1),
+    ("FLJ", 2),
+    ("FOB", 2),
+    ("FOM", 1),
+    ("FON", 2),
+    ("GIT", 1),
+    ("INP", 3),
+    ("IT+", 1),
+    ("IT-", 1),
+    ("ITJ", 2),
+    ("LI+", 1),
+    ("ML+", 1),
+    ("MNP", 4),
+    ("MOV", 2),
+    ("MP+", 1),
+    ("MPJ", 1),
+    ("MYB", 1),
+    ("MYD", 1),
+    ("NCJ", 2),
+    ("NUM", 1),
+    ("PS+", 2),
+    ("QUA", 1),
+    ("SIL", 1),
+    ("SK+", 1),
+    ("SK-", 1),
+    ("SKJ", 2),
+    ("SMP", 2),
+    ("SNP", 4),
+    ("SOU", 1),
+    ("SSS", 1),
+    ("TAM", 3),
+    ("TRA", 4),
+    ("UNI", 1),
+    ("UNJ", 2),
+    ("WAI", 1),
+    ("XX1", 1),
+    ("YNJ", 1),
+];
+
+/// How many bytes the arguments of the command `name` (without its `<`)
+/// take up.
+fn arguments_len(name: &[u8]) -> usize {
+    COMMAND_ARGUMENTS
+        .iter()
+        .find(|(command, _)| command.as_bytes() == name)
+        .map_or(0, |(_, n)| (n * 5).saturating_sub(1))
+}
+
+/// Takes the arguments of the command just lexed into its token, as long as
+/// the script doesn't end first.
+fn skip_arguments(lex: &mut logos::Lexer<Token>) {
+    let len = arguments_len(&lex.slice().as_bytes()[1..]);
+    let rest = lex.remainder();
+    if len <= rest.len() && rest.is_char_boundary(len) && !rest[..len].contains(['<', '\n']) {
+        lex.bump(len);
+    }
+}
+
+impl Token {
+    pub fn is_face(&self) -> bool {
+        matches!(
+            self,
+            Token::NormalWidth
+                | Token::SueSmile
+                | Token::SueFrown
+                | Token::SueAngry
+                | Token::SueHurt
+                | Token::BalrogNormal
+                | Token::TorokoNormal
+                | Token::King
+                | Token::TorokoAngry
+                | Token::Jack
+                | Token::Kazuma
+                | Token::TorokoRage
+                | Token::Igor
+                | Token::Jenka
+                | Token::BalrogSmile
+                | Token::MiseryNormal
+                | Token::MiserySmile
+                | Token::BoosterHurt
+                | Token::BoosterNormal
+                | Token::CurlySmile
+                | Token::CurlyFrown
+                | Token::Doctor
+                | Token::Momorin
+                | Token::BalrogHurt
+                | Token::BrokenRobot
+                | Token::CurlyUnknown
+                | Token::MiseryAngry
+                | Token::HumanSue
+                | Token::Itoh
+                | Token::Ballos
+                | Token::CustomFace
+        )
+    }
+}
+
+/// Overrides how the cipher key of a script is chosen, for mods that don't
+/// use the standard one.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CipherKey {
+    /// The key is the byte at this offset instead of `len / 2`, and is stored
+    /// as-is like the standard one.
+    Index(usize),
+    /// Every byte is shifted by this key, which isn't stored in the file.
+    Byte(u8),
+}
+
+/// The offset of the key byte left as-is in `b`, if any, and the key itself.
+fn cipher_key(b: &[u8], key: Option<CipherKey>) -> Result<(Option<usize>, u8)> {
+    let idx = match key {
+        None => b.len() / 2,
+        Some(CipherKey::Index(idx)) => idx,
+        Some(CipherKey::Byte(key)) => return Ok((None, key)),
+    };
+    match b.get(idx) {
+        Some(&key) => Ok((Some(idx), key)),
+        None => bail!(
+            "key index {idx} is past the end of a {}-byte script",
+            b.len()
+        ),
+    }
+}
+
+/// Decrypts a script. Every byte is shifted back by the key, which is the byte
+/// at `len / 2` unless `key` says otherwise; the key byte itself is stored
+/// as-is.
+pub fn tsc_decode(b: Vec<u8>, key: Option<CipherKey>) -> Result<Vec<u8>> {
+    // there's no key byte to decode with, so empty files pass through as-is
+    if b.is_empty() {
+        return Ok(b);
+    }
+    let (enc_idx, enc) = cipher_key(&b, key)?;
+    Ok(b.iter()
+        .enumerate()
+        .map(|(i, c)| match Some(i) == enc_idx {
+            false => c.wrapping_sub(enc),
+            true => *c,
+        })
+        .collect())
+}
+
+/// Guesses whether `decoded` came out of `tsc_decode` as garbage, because the
+/// script wasn't encrypted or was encrypted with a different key.
+pub fn looks_misdecoded(decoded: &[u8]) -> bool {
+    let has_markers = |b: &[u8]| b.windows(4).any(|w| w == b"<MSG" || w == b"<END");
+    if decoded.is_empty() || has_markers(decoded) {
+        return false;
+    }
+    // high bytes are fine, since they may be part of multi-byte text
+    let unprintable = decoded
+        .iter()
+        .filter(|&&c| (c < 0x20 && !b"\r\n\t".contains(&c)) || c == 0x7f)
+        .count();
+    unprintable * 4 > decoded.len()
+}
+
+/// Encrypts a script, using its middle byte (or the one `key` picks) as the
+/// key. That byte is left unchanged, so `tsc_decode` finds the same key
+/// whatever the length of the file and whether or not the middle falls inside
+/// a multi-byte character. It also means the key can't be chosen: a script
+/// whose middle byte changes, as it does with most translations, gets a new
+/// key, and one whose middle byte is `0` isn't encrypted at all.
+pub fn tsc_encode(b: Vec<u8>, key: Option<CipherKey>) -> Result<Vec<u8>> {
+    if b.is_empty() {
+        return Ok(b);
+    }
+    let (enc_idx, enc) = cipher_key(&b, key)?;
+    Ok(b.iter()
+        .enumerate()
+        .map(|(i, c)| match Some(i) == enc_idx {
+            false => c.wrapping_add(enc),
+            true => *c,
+        })
+        .collect())
+}
+
+/// Text encoding of the decrypted script contents.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TextEncoding {
+    /// Used by the English freeware translation and most mods.
+    #[default]
+    #[serde(rename = "utf-8")]
+    Utf8,
+    /// Used by the original Japanese release.
+    #[serde(rename = "shift-jis")]
+    ShiftJis,
+}
+
+impl TextEncoding {
+    fn encoding_rs(self) -> &'static encoding_rs::Encoding {
+        match self {
+            TextEncoding::Utf8 => encoding_rs::UTF_8,
+            TextEncoding::ShiftJis => encoding_rs::SHIFT_JIS,
+        }
+    }
+
+    /// Decodes `b`, also returning whether any malformed sequences had to be
+    /// replaced.
+    pub fn decode(self, b: &[u8]) -> (Cow<'_, str>, bool) {
+        self.encoding_rs().decode_without_bom_handling(b)
+    }
+
+    pub fn encode(self, s: &str) -> Cow<'_, [u8]> {
+        match self {
+            TextEncoding::Utf8 => Cow::Borrowed(s.as_bytes()),
+            TextEncoding::ShiftJis => encoding_rs::SHIFT_JIS.encode(s).0,
+        }
+    }
+
+    /// Whether `c` can be encoded, rather than being replaced by an HTML
+    /// character reference like `&#8364;`.
+    pub fn can_encode(self, c: char) -> bool {
+        match self {
+            TextEncoding::Utf8 => true,
+            TextEncoding::ShiftJis => !encoding_rs::SHIFT_JIS.encode(c.encode_utf8(&mut [0; 4])).2,
+        }
+    }
+
+    /// Decodes `b` like [`TextEncoding::decode`], but writes every byte that
+    /// wouldn't be encoded back the same, such as the game's own glyphs
+    /// outside of the encoding, and control characters other than line
+    /// breaks and tabs as `\x{NN}`, so that they can be edited as text.
+    /// A `\` that would read as the start of one is itself written as
+    /// `\x{5C}`. [`TextEncoding::unescape`] undoes it.
+    pub fn escape(self, b: &[u8]) -> String {
+        let (text, offsets) = self.decode_with_offsets(b);
+        let mut out = String::new();
+        for (i, c) in text.char_indices() {
+            let bytes = &b[offsets[i]..offsets[i + c.len_utf8()]];
+            let kept = *self.encode(c.encode_utf8(&mut [0; 4])) == *bytes
+                && (!c.is_control() || matches!(c, '\r' | '\n' | '\t'))
+                && !(c == '\\' && text[i..].starts_with("\\x{"));
+            match kept {
+                true => out.push(c),
+                false => {
+                    for byte in bytes {
+                        out.push_str(&format!("\\x{{{byte:02X}}}"));
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// Encodes `s` like [`TextEncoding::encode`], turning every `\x{NN}`
+    /// written by [`TextEncoding::escape`] back into the byte `NN`.
+    pub fn unescape(self, s: &str) -> Vec<u8> {
+        let mut out = vec![];
+        let mut rest = s;
+        while let Some(i) = rest.find("\\x{") {
+            let byte = rest
+                .get(i + 3..i + 6)
+                .filter(|arg| arg.ends_with('}') && arg[..2].bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|arg| u8::from_str_radix(&arg[..2], 16).ok());
+            match byte {
+                Some(byte) => {
+                    out.extend_from_slice(&self.encode(&rest[..i]));
+                    out.push(byte);
+                    rest = &rest[i + 6..];
+                }
+                None => {
+                    out.extend_from_slice(&self.encode(&rest[..i + 3]));
+                    rest = &rest[i + 3..];
+                }
+            }
+        }
+        out.extend_from_slice(&self.encode(rest));
+        out
+    }
+
+    /// Decodes `b` like [`TextEncoding::decode`], along with a table mapping
+    /// each byte offset of the decoded text to the offset of the source byte
+    /// it came from.
+    fn decode_with_offsets(self, b: &[u8]) -> (String, Vec<usize>) {
+        let mut decoder = self.encoding_rs().new_decoder_without_bom_handling();
+        let mut text = String::new();
+        let mut offsets = vec![];
+        let mut start = 0;
+        // feeding one byte at a time lets us see exactly which input bytes
+        // each decoded character was produced from. Malformed sequences are
+        // replaced by hand, since the decoder can report one while still
+        // holding on to the bytes after it
+        for i in 0..b.len() {
+            let mut input = &b[i..=i];
+            loop {
+                text.reserve(decoder.max_utf8_buffer_length(input.len()).unwrap_or(16));
+                let before = text.len();
+                let (result, read) = decoder.decode_to_string_without_replacement(
+                    input,
+                    &mut text,
+                    i + 1 == b.len(),
+                );
+                if text.len() > before {
+                    offsets.resize(text.len(), start);
+                    start = i + 1;
+                }
+                input = &input[read..];
+                match result {
+                    encoding_rs::DecoderResult::Malformed(len, after) => {
+                        let end = i + 1 - input.len() - after as usize;
+                        text.push(char::REPLACEMENT_CHARACTER);
+                        offsets.resize(text.len(), end - len as usize);
+                        start = end;
+                    }
+                    encoding_rs::DecoderResult::InputEmpty => break,
+                    encoding_rs::DecoderResult::OutputFull => {}
+                }
+            }
+        }
+        offsets.push(b.len());
+        (text, offsets)
+    }
+}
+
+impl FromStr for TextEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(TextEncoding::Utf8),
+            "shift-jis" | "shift_jis" | "sjis" => Ok(TextEncoding::ShiftJis),
+            _ => Err(anyhow!(
+                "unknown encoding `{s}` (expected `utf-8` or `shift-jis`)"
+            )),
+        }
+    }
+}
+
+/// How the scripts are laid out in the game data folder.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    /// The freeware release: `Head.tsc`, `Credit.tsc` and friends at the top,
+    /// with the stage scripts in `Stage/`.
+    Freeware,
+    /// Cave Story+, which keeps the same files under `base/`.
+    Plus,
+}
+
+impl Layout {
+    /// Globs matching the dialogue scripts, relative to the game data folder.
+    fn patterns(self) -> &'static [&'static str] {
+        match self {
+            Layout::Freeware => &["*.tsc", "Stage/*.tsc"],
+            Layout::Plus => &["base/*.tsc", "base/Stage/*.tsc"],
+        }
+    }
+}
+
+impl FromStr for Layout {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "freeware" => Ok(Layout::Freeware),
+            "plus" | "cs+" => Ok(Layout::Plus),
+            _ => Err(anyhow!(
+                "unknown layout `{s}` (expected `freeware` or `plus`)"
+            )),
+        }
+    }
+}
+
+/// The speeches to keep, going by whether they have a speaker.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SpeechKind {
+    #[default]
+    All,
+    /// Only the text shown without a face.
+    Narration,
+    /// Only what the characters say.
+    Speech,
+}
+
+impl SpeechKind {
+    pub fn matches(self, speech: &Speech) -> bool {
+        match self {
+            SpeechKind::All => true,
+            SpeechKind::Narration => speech.character.is_none(),
+            SpeechKind::Speech => speech.character.is_some(),
+        }
+    }
+}
+
+impl FromStr for SpeechKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "all" => Ok(SpeechKind::All),
+            "narration" => Ok(SpeechKind::Narration),
+            "speech" => Ok(SpeechKind::Speech),
+            _ => Err(anyhow!(
+                "unknown kind `{s}` (expected `all`, `narration` or `speech`)"
+            )),
+        }
+    }
+}
+
+/// A run of dialogue spoken by a single character.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Speech {
+    /// Identifies the speech across re-extractions, so that translations
+    /// stay with it even when the speeches around it change. See
+    /// [`DialogueData::assign_ids`] for how it's derived.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Who says it, named after their face (or the name the face was mapped
+    /// to), or `None` for narration shown without one.
+    #[serde(deserialize_with = "narration_from_np")]
+    pub character: Option<String>,
+    /// The label (`#0200`) of the event the speech is part of.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event: Option<u32>,
+    /// The message box the speech is shown in. It's part of the script
+    /// around the text, so writing keeps it whatever the translation.
+    #[serde(default, skip_serializing_if = "BoxStyle::is_bottom")]
+    pub box_style: BoxStyle,
+    /// The (translated) text segments, along with the range of the original
+    /// text each one replaces. Ranges are byte offsets into
+    /// [`FileData::original`], always on character boundaries, or into
+    /// [`FileData::original_bytes`] when that is present.
+    ///
+    /// Line breaks in the text are always `\n`, although the scripts use
+    /// `\r\n`; writing turns them back (unless the script only uses `\n`).
+    pub text: Vec<(String, Range<usize>)>,
+    /// Where in the decoded script each of the text segments starts.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locations: Vec<Location>,
+    /// What's going on when the speech is said, or who it's said to, for the
+    /// translators. Never written to the game.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    /// A translator's remark on the speech, such as a TODO. Never written to
+    /// the game.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translator_note: Option<String>,
+    /// Whether a translator has marked the speech as finished, which the
+    /// `status` command tracks a project's progress by. `translate` marks the
+    /// speeches it saves.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub done: bool,
+}
+
+/// The kind of message box a speech is shown in, as opened by `<MSG`, `<MS2`
+/// or `<MS3`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BoxStyle {
+    /// `<MSG`: the usual box at the bottom of the screen.
+    #[default]
+    Bottom,
+    /// `<MS2`: no box, just the text at the top of the screen.
+    Invisible,
+    /// `<MS3`: a box at the top of the screen.
+    Top,
+}
+
+impl BoxStyle {
+    fn is_bottom(&self) -> bool {
+        *self == BoxStyle::Bottom
+    }
+}
+
+impl Speech {
+    /// The name to show the speaker by, which is `Narration` for narration.
+    pub fn speaker(&self) -> &str {
+        self.character.as_deref().unwrap_or("Narration")
+    }
+}
+
+/// Reads the `character` of a [`Speech`], where translation files dumped
+/// before narration had its own value call it `NP`.
+fn narration_from_np<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Option<String>, D::Error> {
+    Ok(Option::<String>::deserialize(d)?.filter(|character| character != "NP"))
+}
+
+/// A position in a decoded script, counting from 1. Columns count characters,
+/// and lines end at `\n` (so a `\r\n` ends a line too).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Fills in where each segment of the speeches starts in `text`, the decoded
+/// script their ranges point into.
+pub fn locate_speeches(dialogues: &mut [Vec<Speech>], text: &str) {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    for speech in dialogues.iter_mut().flatten() {
+        speech.locations = speech
+            .text
+            .iter()
+            .map(|(_, range)| {
+                let line = line_starts.partition_point(|&start| start <= range.start);
+                Location {
+                    line,
+                    column: text[line_starts[line - 1]..range.start].chars().count() + 1,
+                }
+            })
+            .collect();
+    }
+}
+
+/// The dialogue extracted from a single script.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileData {
+    pub dialogues: Vec<Vec<Speech>>,
+    pub original: String,
+    /// The decrypted file contents, only kept when they couldn't be decoded
+    /// losslessly into `original`. When present, the `Speech` ranges are
+    /// offsets into these bytes rather than into `original`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_bytes: Option<Vec<u8>>,
+    /// The script's path relative to [`DialogueData::game_data_root`], like
+    /// `Stage/Pens1.tsc`.
+    pub path: PathBuf,
+    /// The SHA-256 of the script as it was read, still encrypted, to check
+    /// that the game data hasn't changed since.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_sha256: Option<String>,
+    /// Whether the bytes in the text that can't be edited as such are
+    /// written as `\x{NN}`: see [`TextEncoding::escape`].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub escaped: bool,
+    /// The key byte the script was encrypted with, left as-is in the file,
+    /// to tell when writing has to change it: see [`tsc_encode`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_byte: Option<u8>,
+}
+
+/// The contents of a translation file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DialogueData {
+    pub game_data_root: PathBuf,
+    #[serde(default)]
+    pub encoding: TextEncoding,
+    /// Whether the scripts were stored without encryption.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub plaintext: bool,
+    /// How the scripts' cipher key was chosen, if not the standard way.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<CipherKey>,
+    /// Display names the `character` fields were mapped to, keyed by face.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub names: BTreeMap<String, String>,
+    /// Whether only the first few dialogues were kept, by
+    /// [`DialogueData::keep_sample`], so the translation can't be written.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sample: bool,
+    pub files: Vec<FileData>,
+}
+
+/// A JSON Schema (draft 2020-12) of the translation file, i.e. of
+/// [`DialogueData`], for editors and other tools to validate it with. It's
+/// written by hand, so it has to be kept in sync with the structs above.
+pub fn translation_schema() -> serde_json::Value {
+    serde_json::json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "doukutsu-extractor translation file",
+        "type": "object",
+        "required": ["game_data_root", "files"],
+        "properties": {
+            "game_data_root": {
+                "description": "The game data folder (or archive) the scripts were dumped from.",
+                "type": "string"
+            },
+            "encoding": {
+                "description": "Text encoding of the decrypted scripts.",
+                "enum": ["utf-8", "shift-jis"],
+                "default": "utf-8"
+            },
+            "plaintext": {
+                "description": "Whether the scripts were stored without encryption.",
+                "type": "boolean",
+                "default": false
+            },
+            "key": {
+                "description": "How the scripts' cipher key was chosen, if not the standard way.",
+                "oneOf": [
+                    {
+                        "type": "object",
+                        "required": ["index"],
+                        "properties": {"index": {"type": "integer", "minimum": 0}},
+                        "additionalProperties": false
+                    },
+                    {
+                        "type": "object",
+                        "required": ["byte"],
+                        "properties": {"byte": {"type": "integer", "minimum": 0, "maximum": 255}},
+                        "additionalProperties": false
+                    }
+                ]
+            },
+            "sample": {
+                "description": "Whether only the first few dialogues were kept, for a preview, so the translation can't be written.",
+                "type": "boolean",
+                "default": false
+            },
+            "names": {
+                "description": "Display names the character fields were mapped to, keyed by face.",
+                "type": "object",
+                "additionalProperties": {"type": "string"}
+            },
+            "files": {"type": "array", "items": {"$ref": "#/$defs/file"}}
+        },
+        "$defs": {
+            "file": {
+                "description": "The dialogue extracted from a single script.",
+                "type": "object",
+                "required": ["dialogues", "original", "path"],
+                "properties": {
+                    "dialogues": {
+                        "type": "array",
+                        "items": {"type": "array", "items": {"$ref": "#/$defs/speech"}}
+                    },
+                    "original": {
+                        "description": "The decrypted, decoded script.",
+                        "type": "string"
+                    },
+                    "original_bytes": {
+                        "description": "The decrypted script, only kept when it couldn't be decoded losslessly. The ranges then point into these bytes.",
+                        "type": "array",
+                        "items": {"type": "integer", "minimum": 0, "maximum": 255}
+                    },
+                    "path": {
+                        "description": "The script's path relative to `game_data_root`, like `Stage/Pens1.tsc`.",
+                        "type": "string"
+                    },
+                    "source_sha256": {
+                        "description": "The SHA-256 of the script as it was read, still encrypted.",
+                        "type": "string",
+                        "pattern": "^[0-9a-f]{64}$"
+                    },
+                    "escaped": {
+                        "description": "Whether bytes that can't be edited as text are written as \\x{NN} in the text.",
+                        "type": "boolean",
+                        "default": false
+                    },
+                    "key_byte": {
+                        "description": "The key byte the script was encrypted with.",
+                        "type": "integer",
+                        "minimum": 0,
+                        "maximum": 255
+                    }
+                }
+            },
+            "speech": {
+                "description": "A run of dialogue spoken by a single character.",
+                "type": "object",
+                "required": ["character", "text"],
+                "properties": {
+                    "id": {
+                        "description": "Identifies the speech across re-extractions.",
+                        "type": "string"
+                    },
+                    "character": {
+                        "description": "Who says it, or null for narration.",
+                        "type": ["string", "null"]
+                    },
+                    "event": {
+                        "description": "The label of the event the speech is part of.",
+                        "type": "integer",
+                        "minimum": 0
+                    },
+                    "box_style": {
+                        "description": "The message box the speech is shown in: <MSG, <MS2 or <MS3.",
+                        "enum": ["bottom", "invisible", "top"],
+                        "default": "bottom"
+                    },
+                    "text": {
+                        "description": "The (translated) text segments, each with the byte range of the original text it replaces. Line breaks are \\n.",
+                        "type": "array",
+                        "items": {
+                            "type": "array",
+                            "prefixItems": [{"type": "string"}, {"$ref": "#/$defs/range"}],
+                            "minItems": 2,
+                            "maxItems": 2
+                        }
+                    },
+                    "locations": {
+                        "description": "Where in the decoded script each segment starts.",
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "required": ["line", "column"],
+                            "properties": {
+                                "line": {"type": "integer", "minimum": 1},
+                                "column": {"type": "integer", "minimum": 1}
+                            }
+                        }
+                    },
+                    "context": {
+                        "description": "Notes for the translators. Never written to the game.",
+                        "type": "string"
+                    },
+                    "translator_note": {
+                        "description": "A translator's remark on the speech. Never written to the game.",
+                        "type": "string"
+                    },
+                    "done": {
+                        "description": "Whether a translator has marked the speech as finished.",
+                        "type": "boolean",
+                        "default": false
+                    }
+                }
+            },
+            "range": {
+                "type": "object",
+                "required": ["start", "end"],
+                "properties": {
+                    "start": {"type": "integer", "minimum": 0},
+                    "end": {"type": "integer", "minimum": 0}
+                }
+            }
+        }
+    })
+}
+
+impl FileData {
+    fn new(dialogues: Vec<Vec<Speech>>, bytes: &[u8], encoding: TextEncoding) -> FileData {
+        let (text, malformed) = encoding.decode(bytes);
+        // some Shift-JIS characters have two encodings, and only one of
+        // them comes back when encoding the text again
+        let lossy = malformed || encoding.encode(&text) != bytes;
+        let mut fd = FileData {
+            dialogues,
+            original: text.into_owned(),
+            original_bytes: None,
+            path: PathBuf::new(),
+            source_sha256: None,
+            escaped: false,
+            key_byte: None,
+        };
+        if lossy {
+            let (_, offsets) = encoding.decode_with_offsets(bytes);
+            for speech in fd.dialogues.iter_mut().flatten() {
+                for (_, range) in speech.text.iter_mut() {
+                    *range = offsets[range.start]..offsets[range.end];
+                }
+            }
+            fd.original_bytes = Some(bytes.to_vec());
+        }
+        fd
+    }
+
+    /// The untranslated text covered by `range`, with `\n` line breaks like
+    /// the text of a [`Speech`].
+    pub fn original_text(&self, range: &Range<usize>, encoding: TextEncoding) -> Cow<'_, str> {
+        let text = match (&self.original_bytes, self.escaped) {
+            (_, true) => {
+                Cow::Owned(encoding.escape(&self.original_source(range.clone(), encoding)))
+            }
+            (Some(bytes), false) => encoding.decode(&bytes[range.clone()]).0,
+            (None, false) => Cow::Borrowed(&self.original[range.clone()]),
+        };
+        match text.contains("\r\n") {
+            true => Cow::Owned(text.replace("\r\n", "\n")),
+            false => text,
+        }
+    }
+
+    /// The original file contents covered by `range`, as encoded bytes.
+    fn original_source(&self, range: Range<usize>, encoding: TextEncoding) -> Cow<'_, [u8]> {
+        match &self.original_bytes {
+            Some(bytes) => Cow::Borrowed(&bytes[range]),
+            None => encoding.encode(&self.original[range]),
+        }
+    }
+
+    /// The whole untranslated file, decrypted.
+    pub fn original_file(&self, encoding: TextEncoding) -> Cow<'_, [u8]> {
+        match &self.original_bytes {
+            Some(bytes) => Cow::Borrowed(bytes),
+            None => encoding.encode(&self.original),
+        }
+    }
+
+    /// The line break the script uses: `\r\n` like the game's own, unless
+    /// its lines only end in `\n`, as after editing it on Linux.
+    fn line_break(&self) -> &'static str {
+        let (crlf, lf) = match &self.original_bytes {
+            Some(bytes) => (
+                bytes.windows(2).any(|w| w == b"\r\n"),
+                bytes.contains(&b'\n'),
+            ),
+            None => (self.original.contains("\r\n"), self.original.contains('\n')),
+        };
+        match lf && !crlf {
+            true => "\n",
+            false => "\r\n",
+        }
+    }
+
+    /// The command (without its `<`) whose arguments the segment at `range`
+    /// starts in, if any. Scripts dumped before arguments were recognized as
+    /// part of their command, like the `O38` of `<SOU0O38`, can have these.
+    pub fn argument_of(&self, range: &Range<usize>) -> Option<&'static str> {
+        let bytes = match &self.original_bytes {
+            Some(bytes) => bytes.as_slice(),
+            None => self.original.as_bytes(),
+        };
+        let from = range.start.saturating_sub(24);
+        let lt = from
+            + bytes
+                .get(from..range.start)?
+                .iter()
+                .rposition(|&b| b == b'<')?;
+        let name = bytes.get(lt + 1..lt + 4)?;
+        let (command, _) = COMMAND_ARGUMENTS
+            .iter()
+            .find(|(command, _)| command.as_bytes() == name)?;
+        (range.start >= lt + 4 && range.start < lt + 4 + arguments_len(name)).then_some(*command)
+    }
+
+    /// Checks that the segments' ranges can be sliced out of the original
+    /// text: that none of them overlap, run past the end of the file or
+    /// (unless [`FileData::original_bytes`] is kept) split a character, which
+    /// can happen if the translation file was edited by hand.
+    pub fn check_segments(&self) -> Result<()> {
+        let len = match &self.original_bytes {
+            Some(bytes) => bytes.len(),
+            None => self.original.len(),
+        };
+        let mut ranges: Vec<_> = self
+            .dialogues
+            .iter()
+            .flatten()
+            .flat_map(|speech| &speech.text)
+            .map(|(_, range)| range)
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+        let mut last_range_end = 0;
+        for range in ranges {
+            if range.start < last_range_end {
+                bail!(
+                    "{:?}: segment at {}..{} overlaps the previous one, which ends at {}",
+                    self.path,
+                    range.start,
+                    range.end,
+                    last_range_end
+                );
+            }
+            if range.end > len || range.start > range.end {
+                bail!(
+                    "{:?}: segment at {}..{} is outside of the file, which is {} bytes long",
+                    self.path,
+                    range.start,
+                    range.end,
+                    len
+                );
+            }
+            let on_boundaries = |i| self.original.is_char_boundary(i);
+            if self.original_bytes.is_none()
+                && !(on_boundaries(range.start) && on_boundaries(range.end))
+            {
+                bail!(
+                    "{:?}: segment at {}..{} splits a character of the original text",
+                    self.path,
+                    range.start,
+                    range.end
+                );
+            }
+            last_range_end = range.end;
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the decrypted file with the translated text spliced in. With
+    /// `wrap`, translated text is re-flowed into lines of at most that many
+    /// characters.
+    ///
+    /// The file is built up as encoded bytes: everything but the translated
+    /// text is copied from [`FileData::original_bytes`] when decoding was
+    /// lossy (and re-encoded from `original` otherwise, which gives back the
+    /// same bytes), so nothing goes through a lossy `String`. See
+    /// [`FileData::reconstruct`] to display it.
+    ///
+    /// Fails if [`FileData::check_segments`] does.
+    pub fn reconstruct_bytes(
+        &self,
+        encoding: TextEncoding,
+        wrap: Option<usize>,
+    ) -> Result<Vec<u8>> {
+        let len = match &self.original_bytes {
+            Some(bytes) => bytes.len(),
+            None => self.original.len(),
+        };
+        self.check_segments()?;
+        let mut segments: Vec<_> = self
+            .dialogues
+            .iter()
+            .flatten()
+            .flat_map(|speech| &speech.text)
+            .collect();
+        segments.sort_by_key(|(_, range)| range.start);
+        let mut out = vec![];
+        let mut last_range_end = 0;
+        for (text, range) in segments {
+            out.extend_from_slice(&self.original_source(last_range_end..range.start, encoding));
+            // untranslated text is copied verbatim, so that any bytes
+            // lost while decoding survive the round-trip
+            if self.original_text(range, encoding) == text.as_str() {
+                out.extend_from_slice(&self.original_source(range.clone(), encoding));
+            } else {
+                let text = match wrap {
+                    Some(width) => Cow::Owned(wrap_text(text, width)),
+                    None => Cow::Borrowed(text.as_str()),
+                };
+                // translation files from before line breaks were normalized
+                // may still have `\r\n`
+                let text = text.replace("\r\n", "\n").replace('\n', self.line_break());
+                match self.escaped {
+                    true => out.extend(encoding.unescape(&text)),
+                    false => out.extend_from_slice(&encoding.encode(&text)),
+                }
+            }
+            last_range_end = range.end;
+        }
+        // like everything between segments, the tail comes from the original
+        // bytes when decoding was lossy, so it survives byte for byte
+        out.extend_from_slice(&self.original_source(last_range_end..len, encoding));
+        Ok(out)
+    }
+
+    /// [`FileData::reconstruct_bytes`] decoded for display, with malformed
+    /// bytes replaced. Not for writing, since that loses them.
+    pub fn reconstruct(&self, encoding: TextEncoding, wrap: Option<usize>) -> Result<String> {
+        let bytes = self.reconstruct_bytes(encoding, wrap)?;
+        Ok(encoding.decode(&bytes).0.into_owned())
+    }
+}
+
+/// Re-flows `text` into lines of at most `width` characters, breaking only
+/// between words. Each of the translator's own lines is wrapped on its own, so
+/// their line breaks stay, and control codes like `<NOD` or `<NUM0000` don't
+/// count towards the width. Leading and trailing whitespace is kept as-is.
+pub fn wrap_text(text: &str, width: usize) -> String {
+    text.split('\n')
+        .map(|line| wrap_line(line, width))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// [`wrap_text`] for a single line.
+fn wrap_line(text: &str, width: usize) -> String {
+    let body = text.trim();
+    let start = text.len() - text.trim_start().len();
+    let end = start + body.len();
+    let mut lines: Vec<(String, usize)> = vec![];
+    for word in body.split_whitespace() {
+        let len = visible_len(word);
+        match lines.last_mut() {
+            Some((line, line_len)) if *line_len + 1 + len <= width => {
+                *line += " ";
+                *line += word;
+                *line_len += 1 + len;
+            }
+            _ => lines.push((word.to_string(), len)),
+        }
+    }
+    let lines: Vec<_> = lines.into_iter().map(|(line, _)| line).collect();
+    format!("{}{}{}", &text[..start], lines.join("\n"), &text[end..])
+}
+
+/// How many characters of `text` the game shows, leaving out control codes.
+pub fn visible_len(text: &str) -> usize {
+    let codes: usize = control_codes(text).iter().map(|code| code.len()).sum();
+    text.chars().count() - codes
+}
+
+/// The amount of text extracted by a dump.
+#[derive(Debug, Default)]
+pub struct DumpStats {
+    pub scanned: usize,
+    pub files: usize,
+    pub speeches: usize,
+    pub chars: usize,
+    pub chars_by_character: BTreeMap<String, usize>,
+}
+
+impl std::fmt::Display for DumpStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Scanned {} scripts, {} with dialogue: {} speeches, {} characters of text",
+            self.scanned, self.files, self.speeches, self.chars
+        )?;
+        let mut by_character: Vec<_> = self.chars_by_character.iter().collect();
+        by_character.sort_by(|a, b| b.1.cmp(a.1));
+        for (character, chars) in by_character {
+            writeln!(f, "  {character}: {chars}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A translation file whose segments don't fit the original text of their
+/// scripts, as found by [`FileData::check_segments`] when reading it.
+#[derive(Debug)]
+pub struct InvalidSegments(pub String);
+
+impl std::fmt::Display for InvalidSegments {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for InvalidSegments {}
+
+/// The file in a split translation's folder that lists its scripts.
+const SPLIT_INDEX: &str = "index.json";
+
+/// Reads the JSON file at `path`, which may have been saved as UTF-16 or with
+/// a byte order mark by an editor.
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
+    let bytes = std::fs::read(path)?;
+    let json = match encoding_rs::Encoding::for_bom(&bytes) {
+        Some((encoding, bom)) => {
+            debug!("{path:?} starts with a {} byte order mark", encoding.name());
+            let (text, malformed) = encoding.decode_without_bom_handling(&bytes[bom..]);
+            if malformed {
+                bail!("{path:?} isn't valid {}", encoding.name());
+            }
+            Cow::Owned(text.into_owned().into_bytes())
+        }
+        None => Cow::Borrowed(&bytes[..]),
+    };
+    serde_json::from_slice(&json).map_err(|e| json_error(path, &json, e))
+}
+
+/// Writes `value` to `writer` as JSON, indented for hand-editing if `pretty`.
+fn write_json(writer: impl Write, value: &impl Serialize, pretty: bool) -> Result<()> {
+    // serialize straight into the file, since the whole translation can run
+    // into tens of megabytes
+    let mut writer = BufWriter::new(writer);
+    match pretty {
+        true => serde_json::to_writer_pretty(&mut writer, value)?,
+        false => serde_json::to_writer(&mut writer, value)?,
+    };
+    writer.flush()?;
+    Ok(())
+}
+
+/// Explains why `json` couldn't be parsed, quoting the text around the
+/// problem so that it can be found in a big file.
+fn json_error(path: &Path, json: &[u8], e: serde_json::Error) -> anyhow::Error {
+    let line = json
+        .split(|&b| b == b'\n')
+        .nth(e.line().saturating_sub(1))
+        .unwrap_or_default();
+    // columns count bytes from 1; translation files are often a single line,
+    // so only quote a little of it
+    let column = e.column().saturating_sub(1).min(line.len());
+    let before = String::from_utf8_lossy(&line[column.saturating_sub(40)..column]);
+    let after = String::from_utf8_lossy(&line[column..line.len().min(column + 40)]);
+    let message = format!(
+        "{path:?} isn't a valid translation file, at line {} column {}:\n  {before}{}\n  {}^",
+        e.line(),
+        e.column(),
+        after.trim_end(),
+        " ".repeat(before.chars().count())
+    );
+    anyhow::Error::new(e).context(message)
+}
+
+#[derive(Debug, Default)]
+pub struct MergeSummary {
+    pub kept: usize,
+    pub added: usize,
+    pub removed: usize,
+}
+
+impl DialogueData {
+    /// Reads a translation file, which may have been saved as UTF-16 or with
+    /// a byte order mark by an editor, or a folder it was split into with
+    /// [`DialogueData::save_split`].
+    pub fn read(path: &Path) -> Result<DialogueData> {
+        if path.is_dir() {
+            return DialogueData::read_split(path);
+        }
+        let mut dd: DialogueData = read_json(path)?;
+        dd.relativize_paths();
+        dd.check_segments(path)?;
+        Ok(dd)
+    }
+
+    /// Checks the segments of every file, so that nothing slices the original
+    /// text of a hand-edited translation out of bounds. Each file that fails
+    /// is warned about, so they can all be fixed at once.
+    fn check_segments(&self, path: &Path) -> Result<()> {
+        let invalid = self
+            .files
+            .iter()
+            .filter_map(|fd| fd.check_segments().err())
+            .inspect(|e| warn!("{e}"))
+            .count();
+        match invalid {
+            0 => Ok(()),
+            n => Err(InvalidSegments(format!(
+                "{path:?} has {n} scripts whose segments don't fit their original text"
+            ))
+            .into()),
+        }
+    }
+
+    /// Writes the translation file, indented for hand-editing if `pretty`.
+    /// If `path` is a folder, the translation is split up into it.
+    pub fn save(&self, path: &Path, pretty: bool) -> Result<()> {
+        if path.is_dir() {
+            return self.save_split(path, pretty);
+        }
+        self.save_to(std::fs::File::create(path)?, pretty)
+    }
+
+    /// Whether the translation file at `path`, or the index of the folder it
+    /// was split into, is indented, so that saving it in place can keep it
+    /// that way. Compact JSON has no line breaks at all.
+    pub fn is_pretty(path: &Path) -> bool {
+        let path = match path.is_dir() {
+            true => path.join(SPLIT_INDEX),
+            false => path.to_path_buf(),
+        };
+        let mut start = vec![];
+        std::fs::File::open(path)
+            .and_then(|file| file.take(64).read_to_end(&mut start))
+            .is_ok_and(|_| start.contains(&b'\n'))
+    }
+
+    /// Writes the translation file to `writer`, such as stdout.
+    pub fn save_to(&self, writer: impl Write, pretty: bool) -> Result<()> {
+        write_json(writer, self, pretty)
+    }
+
+    /// Writes the translation into the folder `dir`, with each script's
+    /// dialogue in a file of its own at the script's path plus `.json` (e.g.
+    /// `Stage/Pens1.tsc.json`), so that translators working on different
+    /// scripts don't get in each other's way. `index.json` holds the rest,
+    /// with the scripts' paths in place of their dialogue.
+    pub fn save_split(&self, dir: &Path, pretty: bool) -> Result<()> {
+        let mut scripts = vec![];
+        for fd in &self.files {
+            let rel = &fd.path;
+            let mut name = rel.as_os_str().to_owned();
+            name.push(".json");
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap_or(dir))?;
+            write_json(std::fs::File::create(&path)?, fd, pretty)?;
+            scripts.push(rel);
+        }
+        // everything but the files, which are only named
+        let mut index = serde_json::to_value(DialogueData {
+            game_data_root: self.game_data_root.clone(),
+            encoding: self.encoding,
+            plaintext: self.plaintext,
+            key: self.key,
+            names: self.names.clone(),
+            sample: self.sample,
+            files: vec![],
+        })?;
+        index["files"] = serde_json::to_value(scripts)?;
+        write_json(
+            std::fs::File::create(dir.join(SPLIT_INDEX))?,
+            &index,
+            pretty,
+        )
+    }
+
+    /// Reads a translation that was split into `dir` by
+    /// [`DialogueData::save_split`].
+    fn read_split(dir: &Path) -> Result<DialogueData> {
+        let index_path = dir.join(SPLIT_INDEX);
+        let mut index: serde_json::Value = read_json(&index_path)?;
+        let scripts: Vec<PathBuf> = serde_json::from_value(index["files"].take())
+            .map_err(|e| anyhow!("{index_path:?}: `files` should list the scripts' paths: {e}"))?;
+        index["files"] = serde_json::Value::Array(vec![]);
+        let mut dd: DialogueData = serde_json::from_value(index)
+            .map_err(|e| anyhow::Error::new(e).context(format!("{index_path:?} is invalid")))?;
+        for rel in scripts {
+            // an edited index mustn't be able to read anything outside of it
+            if !rel
+                .components()
+                .all(|c| matches!(c, std::path::Component::Normal(_)))
+            {
+                bail!("{index_path:?} lists {rel:?}, which is outside of {dir:?}");
+            }
+            let mut name = rel.into_os_string();
+            name.push(".json");
+            dd.files.push(read_json(&dir.join(name))?);
+        }
+        dd.relativize_paths();
+        dd.check_segments(dir)?;
+        Ok(dd)
+    }
+
+    /// Counts the text in the translation file, out of `scanned` scripts.
+    pub fn stats(&self, scanned: usize) -> DumpStats {
+        let mut stats = DumpStats {
+            scanned,
+            files: self
+                .files
+                .iter()
+                .filter(|fd| !fd.dialogues.is_empty())
+                .count(),
+            ..Default::default()
+        };
+        for fd in &self.files {
+            for speech in fd.dialogues.iter().flatten() {
+                let chars: usize = speech
+                    .text
+                    .iter()
+                    .map(|(_, range)| fd.original_text(range, self.encoding).chars().count())
+                    .sum();
+                stats.speeches += 1;
+                stats.chars += chars;
+                *stats
+                    .chars_by_character
+                    .entry(speech.speaker().to_string())
+                    .or_default() += chars;
+            }
+        }
+        stats
+    }
+
+    /// Keeps only the first `n` dialogues, across the files in order, to
+    /// preview the extraction with, and marks the translation as a
+    /// [`DialogueData::sample`].
+    pub fn keep_sample(&mut self, n: usize) {
+        let mut left = n;
+        for fd in self.files.iter_mut() {
+            fd.dialogues.retain(|dialogue| !dialogue.is_empty());
+            fd.dialogues.truncate(left);
+            left -= fd.dialogues.len();
+        }
+        self.files.retain(|fd| !fd.dialogues.is_empty());
+        self.sample = true;
+    }
+
+    /// Drops the speeches not matching `keep`, along with any dialogues and
+    /// files left empty.
+    pub fn retain_speeches(&mut self, mut keep: impl FnMut(&Speech) -> bool) {
+        for fd in self.files.iter_mut() {
+            for dialogue in fd.dialogues.iter_mut() {
+                dialogue.retain(&mut keep);
+            }
+            fd.dialogues.retain(|dialogue| !dialogue.is_empty());
+        }
+        self.files.retain(|fd| !fd.dialogues.is_empty());
+    }
+
+    /// Makes the files' paths relative to the game data folder, so that the
+    /// translation still works once the game or the project is moved. Older
+    /// versions stored them in full, which is why it's done when reading too.
+    fn relativize_paths(&mut self) {
+        for fd in self.files.iter_mut() {
+            if let Ok(rel) = fd.path.strip_prefix(&self.game_data_root) {
+                fd.path = rel.to_path_buf();
+            }
+        }
+    }
+
+    /// Gives each speech its [`Speech::id`]: the first 16 hex digits of the
+    /// SHA-256 of its file's path relative to the game data folder (with `/`
+    /// between folders), its event label and its original text, each on a
+    /// line of its own, as in `Stage/Pens1.tsc\n0100\nWell... I guess so.`.
+    /// The label is left empty for speeches outside of any event, and the
+    /// text is that of each segment in turn, with `\n` line breaks.
+    ///
+    /// Speeches with the same text in the same event get `-2`, `-3` and so on
+    /// appended, in order. The ids only stay the same between dumps made with
+    /// the same options, since e.g. `--inline-codes` changes the text.
+    pub fn assign_ids(&mut self) {
+        for fd in self.files.iter_mut() {
+            let rel = fd
+                .path
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let mut seen: HashMap<String, usize> = HashMap::new();
+            let mut ids = vec![];
+            for speech in fd.dialogues.iter().flatten() {
+                let event = speech.event.map(|e| format!("{e:04}")).unwrap_or_default();
+                let text: String = speech
+                    .text
+                    .iter()
+                    .map(|(_, range)| fd.original_text(range, self.encoding))
+                    .collect();
+                let id = sha256_hex(format!("{rel}\n{event}\n{text}").as_bytes())[..16].to_string();
+                let n = seen.entry(id.clone()).or_default();
+                *n += 1;
+                ids.push(match n {
+                    1 => id,
+                    n => format!("{id}-{n}"),
+                });
+            }
+            for (speech, id) in fd.dialogues.iter_mut().flatten().zip(ids) {
+                speech.id = Some(id);
+            }
+        }
+    }
+
+    /// Fills in the [`Speech::context`] of the speeches `context` has notes
+    /// for. It's keyed by the path of each file relative to the game data
+    /// folder, like `Stage/Pens1.tsc`, then by either an event label like
+    /// `#0100`, for every speech in the event, or a dialogue and speech index
+    /// like `2:1`. A speech with both gets the event's note first.
+    pub fn add_context(
+        &mut self,
+        context: &BTreeMap<String, BTreeMap<String, String>>,
+    ) -> Result<()> {
+        for (file, notes) in context {
+            let Some(fd) = self.files.iter_mut().find(|fd| fd.path == Path::new(file)) else {
+                warn!("Ignoring the context for {file:?}, which wasn't dumped");
+                continue;
+            };
+            let mut events: HashMap<u32, &str> = HashMap::new();
+            let mut speeches: HashMap<(usize, usize), &str> = HashMap::new();
+            for (key, note) in notes {
+                if let Some(label) = key.strip_prefix('#')
+                    && let Ok(event) = label.parse()
+                {
+                    events.insert(event, note);
+                } else if let Some((d, s)) = key.split_once(':')
+                    && let (Ok(d), Ok(s)) = (d.parse(), s.parse())
+                {
+                    speeches.insert((d, s), note);
+                } else {
+                    bail!(
+                        "context for {file:?}: expected an event label like `#0100` or a \
+                         dialogue and speech index like `2:1`, got {key:?}"
+                    );
+                }
+            }
+            for (d, dialogue) in fd.dialogues.iter_mut().enumerate() {
+                for (s, speech) in dialogue.iter_mut().enumerate() {
+                    let event = speech.event.and_then(|e| events.get(&e));
+                    let own = speeches.remove(&(d, s));
+                    let note: Vec<&str> = event.copied().into_iter().chain(own).collect();
+                    if !note.is_empty() {
+                        speech.context = Some(note.join("\n"));
+                    }
+                }
+            }
+            for (d, s) in speeches.keys() {
+                warn!("Ignoring the context for {file:?} {d}:{s}, which has no such speech");
+            }
+        }
+        Ok(())
+    }
+
+    /// Sorts the files by their path relative to the game data folder.
+    pub fn sort_files(&mut self) {
+        self.files.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+
+    /// Copies each translation over to every untranslated segment with the
+    /// same original text, returning how many were filled in. Where an
+    /// original was translated differently in several places, the first
+    /// translation wins.
+    pub fn propagate_translations(&mut self) -> usize {
+        let mut memory: HashMap<String, String> = HashMap::new();
+        for fd in &self.files {
+            for (text, range) in fd.dialogues.iter().flatten().flat_map(|s| &s.text) {
+                let original = fd.original_text(range, self.encoding);
+                if *text != original {
+                    memory
+                        .entry(original.into_owned())
+                        .or_insert_with(|| text.clone());
+                }
+            }
+        }
+        let mut filled = 0;
+        for fd in self.files.iter_mut() {
+            let mut dialogues = std::mem::take(&mut fd.dialogues);
+            for (text, range) in dialogues
+                .iter_mut()
+                .flatten()
+                .flat_map(|s| s.text.iter_mut())
+            {
+                let original = fd.original_text(range, self.encoding);
+                if *text == original
+                    && let Some(translation) = memory.get(original.as_ref())
+                {
+                    *text = translation.clone();
+                    filled += 1;
+                }
+            }
+            fd.dialogues = dialogues;
+        }
+        filled
+    }
+
+    /// Carries the translations from `old` over to the speeches with the same
+    /// [`Speech::id`], along with their context and notes, wherever they've
+    /// moved. Speeches in `old` without an id, as dumped by older versions,
+    /// are matched by their file path and dialogue/speech index instead.
+    /// Everything else keeps the freshly extracted text.
+    pub fn merge_translations(&mut self, old: DialogueData) -> MergeSummary {
+        let mut summary = MergeSummary {
+            removed: old
+                .files
+                .iter()
+                .flat_map(|fd| fd.dialogues.iter().flatten())
+                .count(),
+            ..Default::default()
+        };
+        let old_files: HashMap<&Path, &FileData> =
+            old.files.iter().map(|fd| (fd.path.as_path(), fd)).collect();
+        let old_ids: HashMap<&str, (&FileData, &Speech)> = old
+            .files
+            .iter()
+            .flat_map(|fd| fd.dialogues.iter().flatten().map(move |s| (fd, s)))
+            .filter_map(|(fd, s)| Some((s.id.as_deref()?, (fd, s))))
+            .collect();
+        for fd in self.files.iter_mut() {
+            let rel = fd.path.as_path();
+            let old_fd = old_files.get(rel);
+            for (d, dialogue) in fd.dialogues.iter_mut().enumerate() {
+                for (s, speech) in dialogue.iter_mut().enumerate() {
+                    let by_id = speech.id.as_deref().and_then(|id| old_ids.get(id)).copied();
+                    let by_index = || {
+                        old_fd.and_then(|ofd| {
+                            ofd.dialogues
+                                .get(d)
+                                .and_then(|od| od.get(s))
+                                .filter(|os| os.id.is_none())
+                                .map(|os| (*ofd, os))
+                        })
+                    };
+                    let Some((old_fd, old_speech)) = by_id.or_else(by_index) else {
+                        summary.added += 1;
+                        continue;
+                    };
+                    summary.kept += 1;
+                    summary.removed -= 1;
+                    // context given with this dump takes precedence
+                    if speech.context.is_none() {
+                        speech.context.clone_from(&old_speech.context);
+                    }
+                    speech
+                        .translator_note
+                        .clone_from(&old_speech.translator_note);
+                    speech.done = old_speech.done;
+                    for ((text, _), (old_text, old_range)) in
+                        speech.text.iter_mut().zip(&old_speech.text)
+                    {
+                        if old_fd.original_text(old_range, old.encoding) != old_text.as_str() {
+                            *text = old_text.clone();
+                        }
+                    }
+                }
+            }
+        }
+        summary
+    }
+}
+
+/// Splits a decrypted script into dialogues, each a list of the speeches made
+/// in a single message box.
+///
+/// Speakers are named after their face, as listed in `faces` by face code
+/// (e.g. `FAC0045`). Faces missing from it keep their vanilla name, or their
+/// code for modded ones.
+pub fn dialogues_from_tsc(text: &str, faces: &BTreeMap<String, String>) -> Vec<Vec<Speech>> {
+    let mut lex = Token::lexer(text);
+    let mut character = None;
+    let mut box_style = BoxStyle::Bottom;
+    let mut event = None;
+    let mut label_next = false;
+    let mut after_text = false;
+    // where the punctuation right before the text to come starts, if any
+    let mut lead_start = None;
+    let mut speech_event = None;
+    let mut speech: Vec<(String, Range<usize>)> = vec![];
+    let mut dialogue: Vec<Speech> = vec![];
+    let mut dialogues: Vec<Vec<Speech>> = vec![];
+    while let Some(Ok(token)) = lex.next() {
+        // an event starts with its label, e.g. `#0200`
+        if label_next && matches!(token, Token::NumCode) {
+            event = lex.slice().parse().ok();
+        }
+        label_next = matches!(token, Token::Pound);
+        let continues_text = matches!(token, Token::Text(_) | Token::Other(_) | Token::Colon);
+        let leads_text = matches!(token, Token::Other(_)) && !after_text;
+        // who speaks from here on, if the token is a face: <FAC0000 takes the
+        // face down, leaving narration
+        let face = match token {
+            Token::NormalWidth => Some(None),
+            _ if token.is_face() => {
+                let code = &lex.slice()[1..];
+                Some(Some(match (faces.get(code), &token) {
+                    (Some(name), _) => name.clone(),
+                    (None, Token::CustomFace) => code.to_string(),
+                    (None, _) => format!("{token:?}"),
+                }))
+            }
+            _ => None,
+        };
+        // putting up the face that's already there doesn't change who's
+        // speaking, so it carries on the same speech
+        let speaker_changes = face.as_ref().is_some_and(|face| *face != character);
+        // a new message box or speaker ends the current speech, as does
+        // clearing the box or locking or freeing the player (which would
+        // otherwise end up in the text with --inline-codes), though the
+        // dialogue carries on after that
+        let box_ends = matches!(
+            token,
+            Token::Message
+                | Token::MessageInvisible
+                | Token::MessageTop
+                | Token::End
+                | Token::Transport
+        );
+        let speech_ends = matches!(token, Token::Clear | Token::Pri | Token::Key | Token::Fre);
+        if (box_ends || speaker_changes || speech_ends) && !speech.is_empty() {
+            dialogue.push(Speech {
+                character: character.clone(),
+                event: speech_event,
+                box_style,
+                text: std::mem::take(&mut speech),
+                locations: vec![],
+                id: None,
+                context: None,
+                translator_note: None,
+                done: false,
+            });
+        }
+        if box_ends && !dialogue.is_empty() {
+            dialogues.push(std::mem::take(&mut dialogue));
+        }
+        // the face stays up across boxes until the event ends, so only a box
+        // opened without one is narration
+        if matches!(token, Token::End | Token::Transport) {
+            character = None;
+        }
+        match token {
+            Token::Message => box_style = BoxStyle::Bottom,
+            Token::MessageInvisible => box_style = BoxStyle::Invisible,
+            Token::MessageTop => box_style = BoxStyle::Top,
+            _ => {}
+        }
+        if let Some(face) = face {
+            character = face;
+        } else if matches!(token, Token::Text(_)) {
+            match speech.last_mut() {
+                // punctuation the lexer doesn't know, like the brackets in
+                // `Hello (friend)`, splits up text that belongs together
+                Some((joined, range)) if after_text => {
+                    range.end = lex.span().end;
+                    *joined = text[range.clone()].replace("\r\n", "\n");
+                }
+                _ => {
+                    // speeches are only pushed once the next one starts,
+                    // which may be in a later event
+                    if speech.is_empty() {
+                        speech_event = event;
+                    }
+                    let range = lead_start.unwrap_or(lex.span().start)..lex.span().end;
+                    speech.push((text[range.clone()].replace("\r\n", "\n"), range));
+                }
+            }
+        }
+        after_text = continues_text;
+        lead_start = match leads_text {
+            true => lead_start.or(Some(lex.span().start)),
+            false => None,
+        };
+    }
+    // scripts normally finish with `<END`, but don't lose the text of one
+    // that doesn't
+    if !speech.is_empty() {
+        dialogue.push(Speech {
+            character,
+            event: speech_event,
+            box_style,
+            text: speech,
+            locations: vec![],
+            id: None,
+            context: None,
+            translator_note: None,
+            done: false,
+        });
+    }
+    if !dialogue.is_empty() {
+        dialogues.push(dialogue);
+    }
+    dialogues
+}
+
+/// The parts of a script the lexer couldn't make sense of, and where they
+/// are. They never end up in the dialogue, so any text among them is lost.
+pub fn unknown_tokens(text: &str) -> Vec<(String, Range<usize>)> {
+    let mut lex = Token::lexer(text);
+    let mut unknown = vec![];
+    while let Some(token) = lex.next() {
+        if matches!(token, Ok(Token::Other(_)) | Err(_)) {
+            unknown.push((lex.slice().to_string(), lex.span()));
+        }
+    }
+    unknown
+}
+
+/// The control codes in `text`, such as `<NOD`, `<FAC0005` or `<NUM0000`,
+/// each with its arguments, in order.
+pub fn control_codes(text: &str) -> Vec<&str> {
+    let is_code_char = |c: u8| c.is_ascii_uppercase() || c.is_ascii_digit() || b"+-".contains(&c);
+    let bytes = text.as_bytes();
+    let mut codes = vec![];
+    for (start, _) in text.match_indices('<') {
+        let name = &bytes[start + 1..bytes.len().min(start + 4)];
+        if name.len() < 3 || !name.iter().all(|&c| is_code_char(c)) {
+            continue;
+        }
+        // arguments are four digits each, separated by colons
+        let digits_at = |i: usize| {
+            bytes
+                .get(i..i + 4)
+                .is_some_and(|d| d.iter().all(u8::is_ascii_digit))
+        };
+        let mut end = start + 4;
+        if digits_at(end) {
+            end += 4;
+            while bytes.get(end) == Some(&b':') && digits_at(end + 1) {
+                end += 5;
+            }
+        }
+        codes.push(&text[start..end]);
+    }
+    codes
+}
+
+/// Whether `path` is the credits script, which has a format of its own.
+pub fn is_credits(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.eq_ignore_ascii_case("Credit.tsc"))
+}
+
+/// Extracts the lines of the credits script, which are the bits of text in
+/// square brackets, e.g. `[Cave Story]`. The rest are one-letter commands
+/// that scroll, show pictures and play music, like `+0160` or `!0003`; each
+/// section starting at a label (`l0100`) becomes a dialogue of its own.
+pub fn credits_from_tsc(text: &str) -> Vec<Vec<Speech>> {
+    let (mut dialogues, mut dialogue) = (vec![], vec![]);
+    let mut label = None;
+    let mut rest = text;
+    while let Some(i) = rest.find(['[', 'l']) {
+        let offset = text.len() - rest.len() + i;
+        let (command, args) = rest[i..].split_at(1);
+        if command == "l" {
+            if let Some(n) = args.get(..4).and_then(|n| n.parse().ok()) {
+                if !dialogue.is_empty() {
+                    dialogues.push(std::mem::take(&mut dialogue));
+                }
+                label = Some(n);
+            }
+            rest = args;
+            continue;
+        }
+        // a line runs up to the closing bracket, which is on the same line
+        let Some(end) = args
+            .find([']', '\n'])
+            .filter(|&end| args[end..].starts_with(']'))
+        else {
+            rest = args;
+            continue;
+        };
+        if !args[..end].trim().is_empty() {
+            dialogue.push(Speech {
+                character: Some("Credits".to_string()),
+                event: label,
+                box_style: BoxStyle::Bottom,
+                text: vec![(args[..end].to_string(), offset + 1..offset + 1 + end)],
+                locations: vec![],
+                id: None,
+                context: None,
+                translator_note: None,
+                done: false,
+            });
+        }
+        rest = &args[end + 1..];
+    }
+    if !dialogue.is_empty() {
+        dialogues.push(dialogue);
+    }
+    dialogues
+}
+
+#[derive(Debug, Default)]
+pub struct DumpOptions {
+    pub encoding: TextEncoding,
+    /// Fail instead of warning when a file doesn't survive the round-trip.
+    pub strict: bool,
+    /// Number of threads to process files with, or all cores if unset.
+    pub jobs: Option<usize>,
+    /// Display names to give speakers, keyed by face.
+    pub names: BTreeMap<String, String>,
+    /// Names of the faces added by mods, keyed by face code.
+    pub faces: BTreeMap<String, String>,
+    /// Notes for the translators to fill [`Speech::context`] with: see
+    /// [`DialogueData::add_context`].
+    pub context: BTreeMap<String, BTreeMap<String, String>>,
+    /// Treat the scripts as already decrypted.
+    pub plaintext: bool,
+    /// Decrypt with this key instead of the standard one.
+    pub key: Option<CipherKey>,
+    /// Where to look for scripts, or everywhere if unset.
+    pub layout: Option<Layout>,
+    /// Glob matching the scripts, relative to the game data folder, for mods
+    /// that name them differently. Takes precedence over `layout`.
+    pub pattern: Option<String>,
+    /// How many folders deep to look for scripts, where 1 is only those
+    /// directly in the game data folder, or any depth if unset.
+    pub max_depth: Option<usize>,
+    /// Keep the control codes within each speech in its text.
+    pub inline_codes: bool,
+    /// Start the text after each `<NOD` with the line breaks right after it:
+    /// see [`nod_breaks`].
+    pub nod_breaks: bool,
+    /// Write the bytes that can't be edited as text as `\x{NN}`: see
+    /// [`TextEncoding::escape`].
+    pub escape_bytes: bool,
+    /// Show a progress bar while going through the files.
+    pub progress: bool,
+    /// Keep the files without any dialogue too, so that writing rebuilds all
+    /// of them.
+    pub include_empty: bool,
+}
+
+/// Moves the line breaks right after each `<NOD` into the text segment that
+/// follows, as in `\nI guess so.`. The lexer only takes line breaks within a
+/// run of text, so otherwise those after a `<NOD` aren't part of any segment,
+/// and translators can't move them like the others. This way every line
+/// break in a message box is a `\n` in the text. The `<NOD` itself stays out
+/// of it, so writing puts it back as it was.
+pub fn nod_breaks(dialogues: &mut [Vec<Speech>], text: &str) {
+    for (segment, range) in dialogues.iter_mut().flatten().flat_map(|s| &mut s.text) {
+        let before = &text[..range.start];
+        let code_end = before.trim_end_matches(['\r', '\n']).len();
+        if code_end < before.len() && before[..code_end].ends_with("<NOD") {
+            range.start = code_end;
+            *segment = text[range.clone()].replace("\r\n", "\n");
+        }
+    }
+}
+
+/// Joins the text segments of each speech into one, which keeps the control
+/// codes between them (and the pauses and clears right after the speech)
+/// visible to translators, e.g. `Well...<WAI0050 I guess.<NOD`. Since the
+/// codes are part of the original text, writing puts back whatever codes the
+/// translation contains.
+pub fn inline_codes(dialogues: &mut [Vec<Speech>], text: &str) {
+    for speech in dialogues.iter_mut().flatten() {
+        let (Some((_, first)), Some((_, last))) = (speech.text.first(), speech.text.last()) else {
+            continue;
+        };
+        let start = first.start;
+        let mut end = last.end;
+        loop {
+            let rest = &text[end..];
+            let wait = rest.len() >= 8
+                && rest.starts_with("<WAI")
+                && rest.as_bytes()[4..8].iter().all(u8::is_ascii_digit);
+            end += match () {
+                _ if rest.starts_with("<NOD") || rest.starts_with("<CLR") => 4,
+                _ if wait => 8,
+                _ => break,
+            };
+        }
+        speech.text = vec![(text[start..end].replace("\r\n", "\n"), start..end)];
+    }
+}
+
+/// The SHA-256 of `bytes`, in lowercase hex.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Extracts the dialogue from a single script, if it has any.
+pub fn dump_file(path: PathBuf, options: &DumpOptions) -> Result<Option<FileData>> {
+    let raw = std::fs::read(&path)?;
+    debug!("Read {} bytes from {path:?}", raw.len());
+    dump_script(path, raw, options)
+}
+
+/// Extracts the dialogue from the contents of the script at `path`, if it has
+/// any.
+pub fn dump_script(path: PathBuf, raw: Vec<u8>, options: &DumpOptions) -> Result<Option<FileData>> {
+    let encoding = options.encoding;
+    let source_sha256 = Some(sha256_hex(&raw));
+    if raw.len() < 2 {
+        if options.include_empty {
+            // a lone byte is its own key, so it's the same decrypted
+            return Ok(Some(FileData {
+                path,
+                source_sha256,
+                ..FileData::new(vec![], &raw, encoding)
+            }));
+        }
+        warn!("Skipping {path:?}: too short to contain any dialogue");
+        return Ok(None);
+    }
+    let key_byte = match options.plaintext {
+        true => None,
+        false => match cipher_key(&raw, options.key) {
+            Ok((Some(_), key)) => Some(key),
+            _ => None,
+        },
+    };
+    let bytes = match options.plaintext {
+        true => raw,
+        false => tsc_decode(raw, options.key).map_err(|e| anyhow!("{path:?}: {e}"))?,
+    };
+    if !options.plaintext && looks_misdecoded(&bytes) {
+        warn!(
+            "{path:?} doesn't look like a script after decrypting it; it may use a \
+             non-standard key (try --key-index or --key-byte), or not be encrypted at \
+             all (try --no-decrypt)"
+        );
+    }
+    let (text, lossy) = encoding.decode(&bytes);
+    debug!(
+        "Decoded {path:?} as {encoding:?}{}",
+        if lossy {
+            ", replacing malformed bytes"
+        } else {
+            ""
+        }
+    );
+    let credits = is_credits(&path);
+    let mut dialogues = match credits {
+        true => credits_from_tsc(&text),
+        false => dialogues_from_tsc(&text, &options.faces),
+    };
+    // the credits have no control codes within their lines
+    if options.nod_breaks && !credits {
+        nod_breaks(&mut dialogues, &text);
+    }
+    if options.inline_codes && !credits {
+        inline_codes(&mut dialogues, &text);
+    }
+    locate_speeches(&mut dialogues, &text);
+    debug!("Lexed {} dialogues from {path:?}", dialogues.len());
+    if dialogues.is_empty() && !options.include_empty {
+        warn!("Skipping {path:?}: no dialogue found");
+        return Ok(None);
+    }
+    for speech in dialogues.iter_mut().flatten() {
+        if let Some(name) = speech.character.as_ref().and_then(|c| options.names.get(c)) {
+            speech.character = Some(name.clone());
+        }
+    }
+    let mut data = FileData {
+        path,
+        source_sha256,
+        key_byte,
+        ..FileData::new(dialogues, &bytes, encoding)
+    };
+    if options.escape_bytes {
+        data.escaped = true;
+        let mut dialogues = std::mem::take(&mut data.dialogues);
+        for (text, range) in dialogues.iter_mut().flatten().flat_map(|s| &mut s.text) {
+            *text = data.original_text(range, encoding).into_owned();
+        }
+        data.dialogues = dialogues;
+    }
+    // reconstructing the untranslated file must give back exactly
+    // what we read, otherwise writing it would corrupt the script
+    let reconstructed = data.reconstruct_bytes(encoding, None)?;
+    if reconstructed != bytes {
+        let offset = reconstructed
+            .iter()
+            .zip(&bytes)
+            .position(|(a, b)| a != b)
+            .unwrap_or(reconstructed.len().min(bytes.len()));
+        let message = format!(
+            "{:?} doesn't reconstruct to its original contents (first difference at byte {offset})",
+            data.path
+        );
+        match options.strict {
+            true => return Err(anyhow!(message)),
+            false => warn!("{message}"),
+        }
+    }
+    Ok(Some(data))
+}
+
+/// The folder the paths in a translation are relative to: `game_data` itself
+/// (even if it's a zip archive), or the folder holding it when it's a single
+/// script (the one above, for a
+/// stage script, so it keeps its place under `Stage/` when written).
+pub fn game_data_root(game_data: &Path) -> PathBuf {
+    if !game_data.is_file() || is_zip(game_data) {
+        return game_data.to_path_buf();
+    }
+    let parent = game_data.parent().unwrap_or(Path::new(""));
+    match parent.file_name() {
+        Some(name) if name.eq_ignore_ascii_case("stage") => {
+            parent.parent().unwrap_or(Path::new("")).to_path_buf()
+        }
+        _ => parent.to_path_buf(),
+    }
+}
+
+/// The globs the scripts are found with, relative to the game data folder.
+fn script_patterns(options: &DumpOptions) -> Vec<&str> {
+    match (&options.pattern, options.layout) {
+        (Some(pattern), _) => vec![pattern],
+        (None, Some(layout)) => layout.patterns().to_vec(),
+        (None, None) => vec!["**/*.tsc"],
+    }
+}
+
+/// Whether the script at `rel`, relative to the game data folder, is no
+/// deeper than [`DumpOptions::max_depth`].
+fn within_depth(rel: &Path, options: &DumpOptions) -> bool {
+    options
+        .max_depth
+        .is_none_or(|depth| rel.components().count() <= depth)
+}
+
+/// How the script globs are matched: scripts extracted on Windows may come out
+/// as e.g. `HEAD.TSC`.
+const SCRIPT_MATCHING: MatchOptions = MatchOptions {
+    case_sensitive: false,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Whether the game data at `path` is a zip archive rather than a folder or a
+/// script, going by its first bytes, or its extension if it can't be read.
+pub fn is_zip(path: &Path) -> bool {
+    if !path.is_file() {
+        return false;
+    }
+    let mut magic = [0; 4];
+    match std::fs::File::open(path).and_then(|mut f| f.read_exact(&mut magic)) {
+        Ok(()) => magic == *b"PK\x03\x04",
+        Err(_) => path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("zip")),
+    }
+}
+
+/// Reads the scripts in the zip archive at `archive` that `options` pick out,
+/// like [`find_scripts`] does for a folder. Each comes with its path as if
+/// the archive were a folder, e.g. `mod.zip/Stage/Pens1.tsc`.
+pub fn zip_scripts(archive: &Path, options: &DumpOptions) -> Result<Vec<(PathBuf, Vec<u8>)>> {
+    let patterns = script_patterns(options)
+        .into_iter()
+        .map(glob::Pattern::new)
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut zip = zip::ZipArchive::new(std::fs::File::open(archive)?)
+        .map_err(|e| anyhow::Error::new(e).context(format!("couldn't open {archive:?}")))?;
+    let mut scripts = vec![];
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i)?;
+        // entries like `../evil.tsc` would otherwise be written outside of
+        // the output directory
+        let Some(name) = entry.enclosed_name() else {
+            warn!("Skipping {:?} in {archive:?}: unsafe path", entry.name());
+            continue;
+        };
+        if entry.is_dir()
+            || !within_depth(&name, options)
+            || !patterns
+                .iter()
+                .any(|p| p.matches_path_with(&name, SCRIPT_MATCHING))
+        {
+            continue;
+        }
+        let mut bytes = vec![];
+        entry.read_to_end(&mut bytes)?;
+        debug!("Read {} bytes from {name:?} in {archive:?}", bytes.len());
+        scripts.push((archive.join(name), bytes));
+    }
+    Ok(scripts)
+}
+
+/// Finds the scripts in the game data folder: those matching the `pattern`
+/// of `options`, those where its `layout` keeps them, or every script in any
+/// subfolder without either, down to its `max_depth`. If `data_dir` is a
+/// single script, that's the only one.
+pub fn find_scripts(data_dir: &Path, options: &DumpOptions) -> Result<Vec<PathBuf>> {
+    if data_dir.is_file() {
+        return Ok(vec![data_dir.to_path_buf()]);
+    }
+    let mut paths = vec![];
+    for pattern in script_patterns(options) {
+        let pattern = data_dir.join(pattern);
+        paths.extend(
+            glob_with(
+                pattern
+                    .to_str()
+                    .ok_or(anyhow!("couldn't stringify pattern"))?,
+                SCRIPT_MATCHING,
+            )?
+            .flatten()
+            .filter(|path| within_depth(path.strip_prefix(data_dir).unwrap_or(path), options)),
+        );
+    }
+    Ok(paths)
+}
+
+/// A bar counting up to `len` files, or a hidden one unless `show` is set.
+fn progress_bar(show: bool, len: usize) -> ProgressBar {
+    match show {
+        true => ProgressBar::new(len as u64),
+        false => ProgressBar::hidden(),
+    }
+}
+
+/// Extracts the dialogue from the scripts at `paths`, which live in
+/// `data_dir`.
+pub fn dump_scripts(
+    data_dir: PathBuf,
+    paths: Vec<PathBuf>,
+    options: &DumpOptions,
+) -> Result<DialogueData> {
+    dump_each(data_dir, paths, options, |path| dump_file(path, options))
+}
+
+/// Extracts the dialogue from scripts that have already been read, such as
+/// those from [`zip_scripts`], each given with its path in `data_dir`.
+pub fn dump_contents(
+    data_dir: PathBuf,
+    scripts: Vec<(PathBuf, Vec<u8>)>,
+    options: &DumpOptions,
+) -> Result<DialogueData> {
+    dump_each(data_dir, scripts, options, |(path, raw)| {
+        dump_script(path, raw, options)
+    })
+}
+
+/// Extracts the dialogue from each of `scripts` with `dump`.
+fn dump_each<T: Send>(
+    data_dir: PathBuf,
+    scripts: Vec<T>,
+    options: &DumpOptions,
+    dump: impl Fn(T) -> Result<Option<FileData>> + Send + Sync,
+) -> Result<DialogueData> {
+    // every script is independent, so they can all be processed at once
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.jobs.unwrap_or(0))
+        .build()?;
+    let files: Vec<FileData> = pool
+        .install(|| {
+            let progress = progress_bar(options.progress, scripts.len());
+            let files = scripts
+                .into_par_iter()
+                .progress_with(progress.clone())
+                .map(dump)
+                .collect::<Result<Vec<_>>>();
+            progress.finish_and_clear();
+            files
+        })?
+        .into_iter()
+        .flatten()
+        .collect();
+
+    let mut dialogue = DialogueData {
+        game_data_root: data_dir,
+        encoding: options.encoding,
+        plaintext: options.plaintext,
+        key: options.key,
+        names: options.names.clone(),
+        sample: false,
+        files,
+    };
+    dialogue.relativize_paths();
+    // glob's ordering differs between platforms, so sort to keep the output
+    // reproducible
+    dialogue.sort_files();
+    dialogue.assign_ids();
+    dialogue.add_context(&options.context)?;
+    Ok(dialogue)
+}
+
+/// Extracts the dialogue from every script in the game data folder or zip
+/// archive.
+pub fn dump_to_data(data_dir: PathBuf, options: &DumpOptions) -> Result<DialogueData> {
+    if is_zip(&data_dir) {
+        let scripts = zip_scripts(&data_dir, options)?;
+        return dump_contents(data_dir, scripts, options);
+    }
+    let paths = find_scripts(&data_dir, options)?;
+    dump_scripts(game_data_root(&data_dir), paths, options)
+}
+
+/// The names of the vanilla scripts and the folder holding the stage ones,
+/// spelled the way the game looks them up.
+const CANONICAL_NAMES: &str = "\
+    Stage base Head ArmsItem Credit StageSelect 0 Pens1 Eggs EggX Egg6 EggR Weed \
+    Santa Chako MazeI Sand Mimi Cave Start Barr Pool Cemet Plant Shelt Comu \
+    MiBox EgEnd1 Cthu Egg1 Pens2 Malco WeedS WeedD Frog Curly WeedB Stream \
+    CurlyS Jenka1 Dark Gard Jenka2 SandE MazeH MazeW MazeO MazeD MazeA MazeB \
+    MazeS MazeM Drain Almond River Eggs2 Cthu2 EggR2 EggX2 Oside EgEnd2 Itoh \
+    Cent Jail1 Momo Lounge CentW Jail2 Blcny1 Priso1 Ring1 Ring2 Prefa1 Priso2 \
+    Ring3 Little Blcny2 Fall Kings Pixel e_Maze e_Jenk e_Malc e_Ceme e_Sky \
+    Prefa2 Hell1 Hell2 Hell3 Mapi Hell4 Hell42 Statue Ballo1 Ostep e_Labo Pole \
+    Island Ballo2 e_Blcn Clock";
+
+/// Respells the components of `rel` that match a vanilla name regardless of
+/// case, e.g. `STAGE/PENS1.TSC` to `Stage/Pens1.TSC`. With `force_extension`,
+/// the extension is made `.tsc` as well.
+pub fn canonical_path(rel: &Path, normalize_case: bool, force_extension: bool) -> PathBuf {
+    let mut out = PathBuf::new();
+    let mut components = rel.components().peekable();
+    while let Some(component) = components.next() {
+        let is_file = components.peek().is_none();
+        let name = component.as_os_str().to_string_lossy();
+        let (stem, extension) = match is_file {
+            true => name.rsplit_once('.').unwrap_or((&name, "")),
+            false => (name.as_ref(), ""),
+        };
+        let stem = match normalize_case {
+            true => CANONICAL_NAMES
+                .split_whitespace()
+                .find(|canonical| canonical.eq_ignore_ascii_case(stem))
+                .unwrap_or(stem),
+            false => stem,
+        };
+        let extension = match force_extension && is_file {
+            true => "tsc",
+            false => extension,
+        };
+        match extension.is_empty() {
+            true => out.push(stem),
+            false => out.push(format!("{stem}.{extension}")),
+        }
+    }
+    out
+}
+
+#[derive(Debug, Default)]
+pub struct WriteOptions {
+    /// Re-flow translated text into lines of at most this many characters.
+    pub wrap: Option<usize>,
+    /// Rewrite files even if they're already up to date.
+    pub force: bool,
+    /// Copy each file about to be overwritten to `<path>.bak` first, unless
+    /// that backup already exists.
+    pub backup: bool,
+    /// Respell file and folder names the way the game expects them.
+    pub normalize_case: bool,
+    /// Give every output file the `.tsc` extension.
+    pub force_extension: bool,
+    /// Go through every step without touching the disk, listing the files that
+    /// would be written and those that would fail.
+    pub check: bool,
+    /// Show a progress bar while going through the files.
+    pub progress: bool,
+    /// Only write the files whose translation changes them, for a patch that
+    /// goes on top of the game's own files.
+    pub only_changed: bool,
+    /// Number of threads to write files with, or all cores if unset.
+    pub jobs: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+pub struct WriteSummary {
+    pub written: Vec<PathBuf>,
+    /// Files left alone because they were already up to date.
+    pub skipped: Vec<PathBuf>,
+    /// Files not written because nothing in them is translated, with
+    /// [`WriteOptions::only_changed`].
+    pub unchanged: Vec<PathBuf>,
+    /// Backups made of the overwritten files.
+    pub backups: Vec<PathBuf>,
+    /// Files in the translation that couldn't be placed in the output
+    /// directory, or (when checking) rebuilt, with the reason why.
+    pub failed: Vec<(PathBuf, anyhow::Error)>,
+}
+
+/// What became of one file of the translation.
+enum FileOutcome {
+    /// Written to the path, after backing up the file there to the backup.
+    Written(PathBuf, Option<PathBuf>),
+    /// Left alone because it was already up to date.
+    Skipped(PathBuf),
+    /// Left out because nothing in it is translated.
+    Unchanged(PathBuf),
+}
+
+/// Rebuilds the translated game files into `output_dir`. Files are written in
+/// parallel, and one that fails doesn't keep the others from being written:
+/// its error is logged and it's listed in [`WriteSummary::failed`].
+pub fn write_from_data(
+    dd: &DialogueData,
+    output_dir: &Path,
+    options: &WriteOptions,
+) -> Result<WriteSummary> {
+    if !options.check {
+        std::fs::create_dir_all(output_dir)?;
+    }
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.jobs.unwrap_or(0))
+        .build()?;
+    let results: Vec<_> = pool.install(|| {
+        let progress = progress_bar(options.progress, dd.files.len());
+        let results = dd
+            .files
+            .par_iter()
+            .progress_with(progress.clone())
+            .map(|fd| (fd, write_file(dd, fd, output_dir, options)))
+            .collect();
+        progress.finish_and_clear();
+        results
+    });
+    let mut summary = WriteSummary::default();
+    for (fd, result) in results {
+        match result {
+            Ok(FileOutcome::Written(p, backup)) => {
+                summary.written.push(p);
+                summary.backups.extend(backup);
+            }
+            Ok(FileOutcome::Skipped(p)) => summary.skipped.push(p),
+            Ok(FileOutcome::Unchanged(p)) => summary.unchanged.push(p),
+            Err(e) => {
+                warn!("{e:#}");
+                summary.failed.push((fd.path.clone(), e));
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// Rebuilds a single file of the translation into `output_dir`.
+fn write_file(
+    dd: &DialogueData,
+    fd: &FileData,
+    output_dir: &Path,
+    options: &WriteOptions,
+) -> Result<FileOutcome> {
+    let rel = &fd.path;
+    // the paths are relative to the game data folder, and an edited
+    // translation file mustn't be able to write anywhere but the output
+    // directory
+    if !rel
+        .components()
+        .all(|c| matches!(c, std::path::Component::Normal(_)))
+    {
+        bail!(
+            "refusing to write {:?}, which would end up outside of {output_dir:?}",
+            fd.path
+        );
+    }
+    let p = output_dir.join(canonical_path(
+        rel,
+        options.normalize_case,
+        options.force_extension,
+    ));
+    let rebuilt = fd.reconstruct_bytes(dd.encoding, options.wrap)?;
+    if options.only_changed && rebuilt == *fd.original_file(dd.encoding) {
+        return Ok(FileOutcome::Unchanged(p));
+    }
+    let enc = match dd.plaintext {
+        true => rebuilt,
+        false => tsc_encode(rebuilt, dd.key)?,
+    };
+    if let Some((old, new)) = key_change(fd, &enc, dd.key) {
+        match old {
+            0 => warn!(
+                "{:?} wasn't encrypted, having a key of 0, but is rebuilt with the key {new:#04x}",
+                fd.path
+            ),
+            _ => debug!(
+                "{:?}: the key changes from {old:#04x} to {new:#04x}",
+                fd.path
+            ),
+        }
+    }
+    // leave identical files alone so that their timestamps don't change
+    if !options.force && std::fs::read(&p).is_ok_and(|existing| existing == enc) {
+        return Ok(FileOutcome::Skipped(p));
+    }
+    if options.check {
+        return Ok(FileOutcome::Written(p, None));
+    }
+    // files in the same folder are written at the same time, but
+    // create_dir_all doesn't mind another thread creating it first
+    let parent = p
+        .parent()
+        .ok_or(anyhow!("{p:?}: couldn't create parent directory"))?;
+    std::fs::create_dir_all(parent).with_context(|| format!("{parent:?}"))?;
+    let mut backup = None;
+    if options.backup && p.exists() {
+        let mut path = p.clone().into_os_string();
+        path.push(".bak");
+        let path = PathBuf::from(path);
+        // the first backup is the one holding the original file
+        if !path.exists() {
+            std::fs::copy(&p, &path).with_context(|| format!("{path:?}"))?;
+            backup = Some(path);
+        }
+    }
+    std::fs::write(&p, &enc).with_context(|| format!("{p:?}"))?;
+    Ok(FileOutcome::Written(p, backup))
+}
+
+/// The key `fd` was dumped with and the one it's rebuilt with as `enc`, if
+/// they differ. The game takes the key from the middle of the file, so it can
+/// only be kept while the byte there stays the same.
+fn key_change(fd: &FileData, enc: &[u8], key: Option<CipherKey>) -> Option<(u8, u8)> {
+    match (fd.key_byte, cipher_key(enc, key)) {
+        (Some(old), Ok((Some(_), new))) if old != new => Some((old, new)),
+        _ => None,
+    }
+}
+
+/// Reads a file listing every glyph the game's font can render. Line breaks
+/// are always allowed and don't need to be listed.
+pub fn read_charset(path: &Path) -> Result<HashSet<char>> {
+    let mut charset: HashSet<char> = std::fs::read_to_string(path)?.chars().collect();
+    charset.extend(['\r', '\n']);
+    Ok(charset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Dumps the data directory `dir` holding `scripts`, given by their path in
+    /// it and their decrypted contents.
+    fn dump_dir(dir: &Path, scripts: &[(&str, &[u8])]) -> DialogueData {
+        for (path, script) in scripts {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, tsc_encode(script.to_vec(), None).unwrap()).unwrap();
+        }
+        dump_to_data(dir.to_path_buf(), &DumpOptions::default()).unwrap()
+    }
+
+    /// Dumps `script` as `Test.tsc`.
+    fn dump(script: &[u8]) -> FileData {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut dd = dump_dir(tmp.path(), &[("Test.tsc", script)]);
+        dd.files.remove(0)
+    }
+
+    /// Dumps `script` as `Test.tsc` and writes it back without translating
+    /// anything, giving the rebuilt file.
+    fn rewrite(script: &[u8]) -> Vec<u8> {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let dd = dump_dir(&dir.join("data"), &[("Test.tsc", script)]);
+        let out = dir.join("out");
+        write_from_data(&dd, &out, &WriteOptions::default()).unwrap();
+        std::fs::read(out.join("Test.tsc")).unwrap()
+    }
+
+    #[test]
+    fn empty_and_one_byte_scripts_round_trip() {
+        for script in [&b""[..], b"x"] {
+            let decoded = tsc_decode(script.to_vec(), None).unwrap();
+            assert_eq!(decoded, script);
+            assert_eq!(tsc_encode(decoded, None).unwrap(), script);
+        }
+        // and have no dialogue to dump
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::write(dir.join("Empty.tsc"), b"").unwrap();
+        std::fs::write(dir.join("One.tsc"), b"x").unwrap();
+        let dd = dump_to_data(dir.to_path_buf(), &DumpOptions::default()).unwrap();
+        assert!(dd.files.is_empty());
+    }
+
+    #[test]
+    fn high_bytes_outside_the_text_survive() {
+        let mut script = b"#0100\r\n<MSGHello.<NOD<END\r\n".to_vec();
+        script.extend((0x80..=0xff).chain([b'\r', b'\n']));
+        script.extend(b"#0200\r\n<MSGBye.<NOD<END\r\n");
+        assert_eq!(rewrite(&script), tsc_encode(script.clone(), None).unwrap());
+        let fd = dump(&script);
+        assert!(fd.original_bytes.is_some());
+        let rebuilt = fd.reconstruct_bytes(TextEncoding::Utf8, None).unwrap();
+        assert_eq!(rebuilt, script);
+        // only the version for display loses them
+        let shown = fd.reconstruct(TextEncoding::Utf8, None).unwrap();
+        assert!(shown.contains('\u{FFFD}') && shown.ends_with("<MSGBye.<NOD<END\r\n"));
+    }
+
+    #[test]
+    fn dumps_are_reproducible() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let script: &[u8] = b"#0100\r\n<MSGHello.<NOD<END\r\n#0200\r\n<MSGBye.<NOD<END\r\n";
+        let scripts = [
+            ("Stage/Pens1.tsc", script),
+            ("Head.tsc", script),
+            ("Stage/Cave.tsc", script),
+            ("ArmsItem.tsc", script),
+        ];
+        let json = |name: &str| {
+            let path = dir.join(name);
+            dump_dir(&dir.join("data"), &scripts)
+                .save(&path, false)
+                .unwrap();
+            std::fs::read(path).unwrap()
+        };
+        let first = json("first.json");
+        let dd: DialogueData = serde_json::from_slice(&first).unwrap();
+        let paths: Vec<_> = dd.files.iter().map(|fd| &fd.path).collect();
+        assert_eq!(
+            paths,
+            [
+                "ArmsItem.tsc",
+                "Head.tsc",
+                "Stage/Cave.tsc",
+                "Stage/Pens1.tsc"
+            ]
+            .map(Path::new)
+        );
+        assert_eq!(json("second.json"), first);
+    }
+
+    #[test]
+    fn wrapping_keeps_the_translators_line_breaks() {
+        assert_eq!(wrap_text("line one\nline two", 40), "line one\nline two");
+        assert_eq!(
+            wrap_text("a rather long first line\nshort", 10),
+            "a rather\nlong first\nline\nshort"
+        );
+    }
+
+    #[test]
+    fn wrapping_doesnt_count_control_codes() {
+        // 22 characters shown, but 42 with the codes
+        let text = "You got<WAI0050 <NUM0000 coins<NOD for it.";
+        assert_eq!(wrap_text(text, 25), text);
+        assert_eq!(
+            wrap_text(text, 12),
+            "You got<WAI0050 <NUM0000\ncoins<NOD for\nit."
+        );
+    }
+
+    const TWO_BOXES: &[u8] = b"#0100\r\n<MSGHello.<NOD<CLRGoodbye.<NOD<END\r\n";
+
+    #[test]
+    fn swapped_segments_still_rebuild() {
+        let mut fd = dump(TWO_BOXES);
+        fd.dialogues[0].swap(0, 1);
+        assert_eq!(
+            fd.reconstruct_bytes(TextEncoding::Utf8, None).unwrap(),
+            TWO_BOXES
+        );
+    }
+
+    #[test]
+    fn overlapping_segments_are_refused() {
+        let mut fd = dump(TWO_BOXES);
+        let first = fd.dialogues[0][0].text[0].1.clone();
+        fd.dialogues[0][1].text[0].1 = first.start + 2..first.end + 2;
+        let e = fd.reconstruct_bytes(TextEncoding::Utf8, None).unwrap_err();
+        assert!(e.to_string().contains("overlaps the previous one"), "{e}");
+    }
+
+    #[test]
+    fn segments_past_the_end_are_refused() {
+        let mut fd = dump(TWO_BOXES);
+        fd.dialogues[0][1].text[0].1.end = TWO_BOXES.len() + 10;
+        let e = fd.check_segments().unwrap_err();
+        assert!(e.to_string().contains("is outside of the file"), "{e}");
+    }
+
+    #[test]
+    fn segments_splitting_a_character_are_refused() {
+        let mut fd = dump("#0100\r\n<MSGCafé?<NOD<END\r\n".as_bytes());
+        let range = &mut fd.dialogues[0][0].text[0].1;
+        // inside the two bytes of the é
+        range.start += 4;
+        let e = fd.check_segments().unwrap_err();
+        assert!(e.to_string().contains("splits a character"), "{e}");
+    }
+
+    #[test]
+    fn reading_invalid_segments_fails_instead_of_panicking() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let script = "#0100\r\n<MSGCafé?<NOD<END\r\n".as_bytes();
+        for (name, edit) in [("past-end.json", (0, 100)), ("mid-char.json", (4, 0))] {
+            let mut dd = dump_dir(&dir.join("data"), &[("Test.tsc", script)]);
+            let range = &mut dd.files[0].dialogues[0][0].text[0].1;
+            range.start += edit.0;
+            range.end += edit.1;
+            let path = dir.join(name);
+            dd.save(&path, false).unwrap();
+            let e = DialogueData::read(&path).unwrap_err();
+            assert!(e.is::<InvalidSegments>(), "{name}: {e}");
+        }
+    }
+
+    #[test]
+    fn multibyte_text_survives_encryption() {
+        let keys = [None, Some(CipherKey::Index(1)), Some(CipherKey::Byte(7))];
+        // the padding shifts the middle byte through each byte of the
+        // characters, at both odd and even lengths
+        for pad in 0..6 {
+            let text = format!("{}日本語 — ü", "x".repeat(pad));
+            for key in keys {
+                let encrypted = tsc_encode(text.clone().into_bytes(), key).unwrap();
+                let decrypted = tsc_decode(encrypted, key).unwrap();
+                assert_eq!(decrypted, text.as_bytes(), "{text:?} with {key:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn multibyte_translations_survive_writing() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        for (i, translation) in ["こんにちは", "こんにちは!", "Ça va?"]
+            .into_iter()
+            .enumerate()
+        {
+            let mut dd = dump_dir(&dir.join("data"), &[("Test.tsc", TWO_BOXES)]);
+            dd.files[0].dialogues[0][0].text[0].0 = translation.to_string();
+            let out = dir.join(format!("out{i}"));
+            write_from_data(&dd, &out, &WriteOptions::default()).unwrap();
+            let written = std::fs::read(out.join("Test.tsc")).unwrap();
+            let expected = format!("#0100\r\n<MSG{translation}<NOD<CLRGoodbye.<NOD<END\r\n");
+            assert_eq!(tsc_decode(written, None).unwrap(), expected.as_bytes());
+        }
+    }
+
+    #[test]
+    fn paths_escaping_the_output_are_refused() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let mut dd = dump_dir(&dir.join("data"), &[("Test.tsc", TWO_BOXES)]);
+        dd.files[0].path = PathBuf::from("../../etc/evil");
+        let path = dir.join("texts.json");
+        dd.save(&path, false).unwrap();
+        let dd = DialogueData::read(&path).unwrap();
+        let summary =
+            write_from_data(&dd, &dir.join("out/game"), &WriteOptions::default()).unwrap();
+        let failed: Vec<_> = summary.failed.iter().map(|(p, _)| p).collect();
+        assert_eq!(failed, [Path::new("../../etc/evil")]);
+        assert!(summary.written.is_empty());
+        assert!(!dir.join("etc/evil").exists());
+    }
+
+    /// The commands the lexer has tokens for, without their arguments, as in
+    /// the `round_trip` fuzz target.
+    const COMMANDS: &[&str] = &[
+        "<MSG", "<NOD", "<CLR", "<END", "<TUR", "<SAT", "<CAT", "<FMU", "<KEY", "<CLO", "<FAC",
+        "<GIT", "<WAI", "<CMU", "<NUM", "<FLJ", "<TRA", "<ANP", "<ML+", "<AM+", "<IT-", "<SOU",
+        "<MS2", "<MS3", "<PRI", "<FRE",
+    ];
+
+    /// A TSC-like script made of commands the lexer knows, with four-digit
+    /// arguments, event labels, text, line breaks and stray bytes.
+    fn script() -> impl Strategy<Value = Vec<u8>> {
+        let command = (
+            0..COMMANDS.len(),
+            proptest::collection::vec(0..10000u32, 0..4),
+        )
+            .prop_map(|(c, args)| {
+                let args: Vec<_> = args.iter().map(|a| format!("{a:04}")).collect();
+                format!("{}{}", COMMANDS[c], args.join(":")).into_bytes()
+            });
+        let text = prop_oneof![
+            proptest::sample::select(
+                &[
+                    "Hello",
+                    " there.",
+                    "...",
+                    "(?)",
+                    "=Map=",
+                    "日本語",
+                    "Café",
+                    "~",
+                    ":",
+                    "0042"
+                ][..]
+            )
+            .prop_map(|text| text.as_bytes().to_vec()),
+            "[ -~]{1,8}".prop_map(String::into_bytes),
+        ];
+        let piece = prop_oneof![
+            3 => command,
+            1 => (0..10000u32).prop_map(|n| format!("#{n:04}").into_bytes()),
+            3 => text,
+            1 => prop_oneof![Just(b"\r\n".to_vec()), Just(b"\n".to_vec())],
+            1 => any::<u8>().prop_map(|b| vec![b]),
+        ];
+        proptest::collection::vec(piece, 0..40).prop_map(|pieces| pieces.concat())
+    }
+
+    /// Options reading a script strictly, in either encoding and with any of
+    /// the options changing how the text is extracted.
+    fn options() -> impl Strategy<Value = DumpOptions> {
+        any::<(bool, bool, bool, bool)>().prop_map(
+            |(shift_jis, inline_codes, nod_breaks, escape_bytes)| DumpOptions {
+                encoding: match shift_jis {
+                    true => TextEncoding::ShiftJis,
+                    false => TextEncoding::Utf8,
+                },
+                inline_codes,
+                nod_breaks,
+                escape_bytes,
+                plaintext: true,
+                strict: true,
+                include_empty: true,
+                ..Default::default()
+            },
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn segments_and_the_gaps_between_them_make_up_the_script(
+            script in script(),
+            options in options(),
+        ) {
+            let fd = dump_script(PathBuf::from("Random.tsc"), script.clone(), &options)
+                .unwrap()
+                .unwrap();
+            let source = match &fd.original_bytes {
+                Some(bytes) => bytes.as_slice(),
+                None => fd.original.as_bytes(),
+            };
+            let mut segments: Vec<_> = fd
+                .dialogues
+                .iter()
+                .flatten()
+                .flat_map(|s| &s.text)
+                .collect();
+            segments.sort_by_key(|(_, range)| range.start);
+            let mut rebuilt = vec![];
+            let mut end = 0;
+            for (text, range) in segments {
+                prop_assert!(end <= range.start && range.end <= source.len());
+                if fd.original_bytes.is_none() {
+                    prop_assert!(fd.original.is_char_boundary(range.start));
+                    prop_assert!(fd.original.is_char_boundary(range.end));
+                }
+                prop_assert_eq!(text, &fd.original_text(range, options.encoding));
+                rebuilt.extend_from_slice(&source[end..range.start]);
+                rebuilt.extend_from_slice(&source[range.clone()]);
+                end = range.end;
+            }
+            rebuilt.extend_from_slice(&source[end..]);
+            prop_assert_eq!(rebuilt, source);
+        }
+
+        #[test]
+        fn scripts_rebuild_exactly(script in script(), options in options()) {
+            // strict dumping already fails on a script that doesn't rebuild
+            let fd = dump_script(PathBuf::from("Random.tsc"), script.clone(), &options)
+                .unwrap()
+                .unwrap();
+            prop_assert_eq!(fd.reconstruct_bytes(options.encoding, None).unwrap(), script.clone());
+            let encrypted = tsc_encode(script.clone(), None).unwrap();
+            prop_assert_eq!(tsc_decode(encrypted, None).unwrap(), script);
+        }
+    }
+
+    #[test]
+    fn text_speed_codes_dont_split_lines() {
+        let fd = dump(b"#0100\r\n<KEY<MSG<SATQuick text, all in one line.<NOD<END\r\n");
+        let text: Vec<_> = fd.dialogues[0][0]
+            .text
+            .iter()
+            .map(|(text, _)| text)
+            .collect();
+        assert_eq!(text, ["Quick text, all in one line."]);
+    }
+
+    #[test]
+    fn high_bytes_in_the_tail_survive_writing() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let tail = b"<END\r\n\x81\x40\xff\xfe\x80\xa0";
+        let mut script = b"#0100\r\n<MSGHello.<NOD".to_vec();
+        script.extend(tail);
+        let raw = tsc_encode(script.clone(), None).unwrap();
+        let data = dir.join("data");
+        std::fs::create_dir_all(&data).unwrap();
+        std::fs::write(data.join("Test.tsc"), &raw).unwrap();
+        for (i, encoding) in [TextEncoding::Utf8, TextEncoding::ShiftJis]
+            .into_iter()
+            .enumerate()
+        {
+            let options = DumpOptions {
+                encoding,
+                ..Default::default()
+            };
+            let mut dd = dump_to_data(data.clone(), &options).unwrap();
+            let out = dir.join(format!("untranslated{i}"));
+            write_from_data(&dd, &out, &WriteOptions::default()).unwrap();
+            assert_eq!(
+                std::fs::read(out.join("Test.tsc")).unwrap(),
+                raw,
+                "{encoding:?}"
+            );
+            // a translation of another length moves the tail along
+            dd.files[0].dialogues[0][0].text[0].0 = "Hello there, everyone.".to_string();
+            let out = dir.join(format!("translated{i}"));
+            write_from_data(&dd, &out, &WriteOptions::default()).unwrap();
+            let written = tsc_decode(std::fs::read(out.join("Test.tsc")).unwrap(), None).unwrap();
+            assert!(written.ends_with(tail), "{encoding:?}: {written:?}");
+        }
+    }
+
+    #[test]
+    fn line_breaks_are_lf_in_the_translation_and_crlf_in_the_script() {
+        let script = b"#0100\r\n<MSGHuzzah!\r\nI am Balrog.\r\n<NOD<CLRI\r\ngo\r\nnow.<NOD<END\r\n";
+        let mut fd = dump(script);
+        assert_eq!(fd.dialogues[0][0].text[0].0, "Huzzah!\nI am Balrog.\n");
+        let mut json = vec![];
+        serde_json::to_writer(&mut json, &fd.dialogues).unwrap();
+        assert!(!String::from_utf8(json).unwrap().contains("\\r"));
+        assert_eq!(
+            fd.reconstruct_bytes(TextEncoding::Utf8, None).unwrap(),
+            script
+        );
+        fd.dialogues[0][0].text[0].0 = "Ha!\nBalrog\nhere.".to_string();
+        assert_eq!(
+            fd.reconstruct_bytes(TextEncoding::Utf8, None).unwrap(),
+            b"#0100\r\n<MSGHa!\r\nBalrog\r\nhere.<NOD<CLRI\r\ngo\r\nnow.<NOD<END\r\n"
+        );
+    }
+
+    #[test]
+    fn translations_saved_with_a_byte_order_mark_are_read() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let script = "#0100\r\n<MSGCafé!<NOD<END\r\n".as_bytes();
+        let json = dir.join("texts.json");
+        dump_dir(&dir.join("data"), &[("Test.tsc", script)])
+            .save(&json, true)
+            .unwrap();
+        let json = std::fs::read_to_string(json).unwrap();
+        let utf8 = [&b"\xef\xbb\xbf"[..], json.as_bytes()].concat();
+        let utf16: Vec<u8> = [0xfeff]
+            .into_iter()
+            .chain(json.encode_utf16())
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        for (name, bytes) in [("utf8-bom.json", utf8), ("utf16le-bom.json", utf16)] {
+            let path = dir.join(name);
+            std::fs::write(&path, bytes).unwrap();
+            let dd = DialogueData::read(&path).unwrap_or_else(|e| panic!("{name}: {e}"));
+            assert_eq!(dd.files[0].dialogues[0][0].text[0].0, "Café!", "{name}");
+        }
+    }
+
+    #[test]
+    fn malformed_translations_are_reported_with_their_line() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("texts.json");
+        std::fs::write(
+            &path,
+            "{\n  \"game_data_root\": \"data\",\n  \"files\": [\n    {\"dialogues\": [] \"original\": \"\"}\n  ]\n}\n",
+        )
+        .unwrap();
+        let e = DialogueData::read(&path).unwrap_err().to_string();
+        assert!(e.contains("at line 4 column 22"), "{e}");
+        assert!(e.contains("{\"dialogues\": [] \"original\""), "{e}");
+    }
+
+    #[test]
+    fn text_split_by_unknown_punctuation_is_one_segment() {
+        let script = b"#0100\r\n<MSG(Psst) Hey & you [over there]!<NOD<END\r\n";
+        let mut fd = dump(script);
+        let text: Vec<_> = fd.dialogues[0][0]
+            .text
+            .iter()
+            .map(|(text, _)| text)
+            .collect();
+        assert_eq!(text, ["(Psst) Hey & you [over there]!"]);
+        fd.dialogues[0][0].text[0].0 = "Hé, toi !".to_string();
+        assert_eq!(
+            fd.reconstruct_bytes(TextEncoding::Utf8, None).unwrap(),
+            "#0100\r\n<MSGHé, toi !<NOD<END\r\n".as_bytes()
+        );
+    }
+
+    #[test]
+    fn merging_follows_speeches_that_moved() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let script = b"#0100\r\n<MSGYes.<NOD<CLO<MSGYes.<NOD<END\r\n#0200\r\n<MSGBye.<NOD<END\r\n";
+        let old = || {
+            let mut old = dump_dir(&dir.join("old"), &[("Test.tsc", script)]);
+            old.files[0].dialogues[1][0].text[0].0 = "Oui.".to_string();
+            old.files[0].dialogues[2][0].text[0].0 = "Au revoir.".to_string();
+            old
+        };
+        let ids: Vec<_> = old().files[0]
+            .dialogues
+            .iter()
+            .flatten()
+            .map(|s| s.id.clone().unwrap())
+            .collect();
+        // the same text twice in an event is told apart by its order
+        assert_eq!(ids[1], format!("{}-2", ids[0]));
+
+        let mut inserted = b"#0050\r\n<MSGNew.<NOD<END\r\n".to_vec();
+        inserted.extend(script);
+        let mut dd = dump_dir(&dir.join("inserted"), &[("Test.tsc", &inserted)]);
+        let summary = dd.merge_translations(old());
+        assert_eq!((summary.kept, summary.added, summary.removed), (3, 1, 0));
+        let texts: Vec<_> = dd.files[0]
+            .dialogues
+            .iter()
+            .flatten()
+            .map(|s| s.text[0].0.as_str())
+            .collect();
+        assert_eq!(texts, ["New.", "Yes.", "Oui.", "Au revoir."]);
+
+        let mut dd = dump_dir(
+            &dir.join("removed"),
+            &[("Test.tsc", b"#0200\r\n<MSGBye.<NOD<END\r\n")],
+        );
+        let summary = dd.merge_translations(old());
+        assert_eq!((summary.kept, summary.added, summary.removed), (1, 0, 2));
+        assert_eq!(dd.files[0].dialogues[0][0].text[0].0, "Au revoir.");
+    }
+
+    /// Checks `value` against the parts of JSON Schema that
+    /// [`translation_schema`] uses, refusing properties it doesn't list.
+    fn check_schema(value: &serde_json::Value, schema: &serde_json::Value, at: &str) {
+        use serde_json::Value;
+        let root = translation_schema();
+        let schema = match schema["$ref"].as_str() {
+            Some(reference) => &root["$defs"][reference.trim_start_matches("#/$defs/")],
+            None => schema,
+        };
+        if let Some(options) = schema["oneOf"].as_array() {
+            let fits = |option: &Value| {
+                let fits = std::panic::catch_unwind(|| check_schema(value, option, at));
+                fits.is_ok()
+            };
+            assert_eq!(options.iter().filter(|o| fits(o)).count(), 1, "{at}");
+            return;
+        }
+        if let Some(options) = schema["enum"].as_array() {
+            assert!(
+                options.contains(value),
+                "{at}: {value} isn't one of {options:?}"
+            );
+        }
+        let types: Vec<_> = match &schema["type"] {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            _ => vec![],
+        };
+        let is = match value {
+            Value::Null => "null",
+            Value::Bool(_) => "boolean",
+            Value::Number(n) if n.is_u64() || n.is_i64() => "integer",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        };
+        assert!(
+            types.is_empty() || types.contains(&is),
+            "{at}: {value} isn't {types:?}"
+        );
+        if let (Some(n), Some(min)) = (value.as_i64(), schema["minimum"].as_i64()) {
+            assert!(n >= min, "{at}: {n} < {min}");
+        }
+        if let (Some(n), Some(max)) = (value.as_i64(), schema["maximum"].as_i64()) {
+            assert!(n <= max, "{at}: {n} > {max}");
+        }
+        match value {
+            Value::Object(object) => {
+                for required in schema["required"].as_array().into_iter().flatten() {
+                    let required = required.as_str().unwrap();
+                    assert!(object.contains_key(required), "{at}: missing {required}");
+                }
+                for (key, value) in object {
+                    let at = format!("{at}.{key}");
+                    match (&schema["properties"][key], &schema["additionalProperties"]) {
+                        (Value::Null, Value::Null | Value::Bool(false)) => {
+                            panic!("{at} isn't in the schema")
+                        }
+                        (Value::Null, additional) => check_schema(value, additional, &at),
+                        (property, _) => check_schema(value, property, &at),
+                    }
+                }
+            }
+            Value::Array(items) => {
+                for (n, item) in items.iter().enumerate() {
+                    let item_schema = match schema["prefixItems"].get(n) {
+                        Some(prefix) => prefix,
+                        None => &schema["items"],
+                    };
+                    check_schema(item, item_schema, &format!("{at}[{n}]"));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn dumped_files_fit_the_schema() {
+        let tmp = tempfile::tempdir().unwrap();
+        let script = b"#0100\r\n<MSG<FAC0005Hello.<NOD<END\r\n\x81";
+        let mut dd = dump_dir(tmp.path(), &[("Test.tsc", script)]);
+        // every optional field, set as it could be
+        dd.names.insert("FAC0005".to_string(), "Toroko".to_string());
+        dd.plaintext = true;
+        dd.key = Some(CipherKey::Index(0));
+        let fd = &mut dd.files[0];
+        fd.escaped = true;
+        let speech = &mut fd.dialogues[0][0];
+        speech.box_style = BoxStyle::Top;
+        speech.context = Some("Context".to_string());
+        speech.translator_note = Some("Note".to_string());
+        speech.done = true;
+        let json = serde_json::to_value(&dd).unwrap();
+        assert!(json["files"][0]["original_bytes"].is_array());
+        assert!(json["files"][0]["source_sha256"].is_string());
+        assert!(json["files"][0]["dialogues"][0][0]["event"].is_u64());
+        assert!(json["files"][0]["dialogues"][0][0]["locations"].is_array());
+        let schema = translation_schema();
+        check_schema(&json, &schema, "$");
+        for key in [Some(CipherKey::Byte(7)), None] {
+            dd.key = key;
+            check_schema(&serde_json::to_value(&dd).unwrap(), &schema, "$");
+        }
+    }
+
+    #[test]
+    fn escaped_glyphs_round_trip() {
+        // stand-ins for glyphs the font has outside of the encoding, like hearts
+        // and arrows
+        let script = b"#0100\r\n<MSGI love you \x05 so much \x81\x82!<NOD\r\nA backslash: \\x{41} stays.<NOD<END\r\n";
+        let options = DumpOptions {
+            escape_bytes: true,
+            plaintext: true,
+            strict: true,
+            ..Default::default()
+        };
+        let mut fd = dump_script(PathBuf::from("Test.tsc"), script.to_vec(), &options)
+            .unwrap()
+            .unwrap();
+        let text: Vec<_> = fd.dialogues[0][0]
+            .text
+            .iter()
+            .map(|(text, _)| text)
+            .collect();
+        assert_eq!(
+            text,
+            [
+                "I love you \\x{05} so much \\x{81}\\x{82}!",
+                "A backslash: \\x{5C}x{41} stays."
+            ]
+        );
+        assert_eq!(
+            fd.reconstruct_bytes(TextEncoding::Utf8, None).unwrap(),
+            script
+        );
+        fd.dialogues[0][0].text[0].0 = "Je t'aime \\x{05} \\x{81}\\x{82} !".to_string();
+        let rebuilt = fd.reconstruct_bytes(TextEncoding::Utf8, None).unwrap();
+        assert!(
+            rebuilt.starts_with(b"#0100\r\n<MSGJe t'aime \x05 \x81\x82 !<NOD"),
+            "{rebuilt:?}"
+        );
+    }
+
+    #[test]
+    fn old_files_with_full_paths_are_read_relative() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let data = dir.join("data");
+        let script = b"#0100\r\n<MSGHello.<NOD<END\r\n";
+        let dd = dump_dir(&data, &[("Stage/Pens1.tsc", script)]);
+        // as older versions saved it, with the full path of each script
+        let mut json = serde_json::to_value(&dd).unwrap();
+        json["files"][0]["path"] = data.join("Stage/Pens1.tsc").to_str().unwrap().into();
+        let path = dir.join("old.json");
+        std::fs::write(&path, json.to_string()).unwrap();
+
+        let dd = DialogueData::read(&path).unwrap();
+        assert_eq!(dd.files[0].path, Path::new("Stage/Pens1.tsc"));
+        dd.save(&path, false).unwrap();
+        let saved: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["files"][0]["path"], "Stage/Pens1.tsc");
+        let summary = write_from_data(&dd, &dir.join("out"), &WriteOptions::default()).unwrap();
+        assert_eq!(summary.written, [dir.join("out/Stage/Pens1.tsc")]);
+        assert_eq!(
+            std::fs::read(dir.join("out/Stage/Pens1.tsc")).unwrap(),
+            std::fs::read(data.join("Stage/Pens1.tsc")).unwrap()
+        );
+    }
+
+    #[test]
+    fn both_kinds_of_line_break_round_trip() {
+        let options = DumpOptions {
+            nod_breaks: true,
+            plaintext: true,
+            strict: true,
+            ..Default::default()
+        };
+        for script in [
+            &b"#0100\r\n<MSGWell...\r\nhm.<NOD\r\nI guess so.<NOD<END\r\n"[..],
+            b"#0100\n<MSGWell...\nhm.<NOD\nI guess so.<NOD<END\n",
+        ] {
+            let mut fd = dump_script(PathBuf::from("Test.tsc"), script.to_vec(), &options)
+                .unwrap()
+                .unwrap();
+            let text: Vec<_> = fd.dialogues[0][0]
+                .text
+                .iter()
+                .map(|(text, _)| text)
+                .collect();
+            assert_eq!(text, ["Well...\nhm.", "\nI guess so."]);
+            assert_eq!(
+                fd.reconstruct_bytes(TextEncoding::Utf8, None).unwrap(),
+                script
+            );
+            let speech = &mut fd.dialogues[0][0];
+            speech.text[0].0 = "Well... hm.".to_string();
+            speech.text[1].0 = "I guess\nso.".to_string();
+            let crlf = script.contains(&b'\r');
+            let expected = "#0100\n<MSGWell... hm.<NODI guess\nso.<NOD<END\n";
+            let expected = match crlf {
+                true => expected.replace('\n', "\r\n"),
+                false => expected.to_string(),
+            };
+            assert_eq!(
+                fd.reconstruct_bytes(TextEncoding::Utf8, None).unwrap(),
+                expected.as_bytes()
+            );
+        }
+    }
+
+    #[test]
+    fn a_key_of_zero_survives_until_the_middle_byte_changes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let options = DumpOptions {
+            strict: true,
+            include_empty: true,
+            ..Default::default()
+        };
+        // the byte in the middle is 0, which leaves the rest unencrypted
+        let half = b"#0100\r\n<MSGHello.<NOD<END\r\n";
+        let raw = [&half[..], b"\0", half].concat();
+        assert_eq!(tsc_encode(raw.clone(), None).unwrap(), raw);
+        let mut dd = dump_contents(
+            dir.to_path_buf(),
+            vec![(dir.join("Test.tsc"), raw.clone())],
+            &options,
+        )
+        .unwrap();
+        assert_eq!(dd.files[0].key_byte, Some(0));
+        let path = dir.join("texts.json");
+        dd.save(&path, false).unwrap();
+        assert_eq!(
+            DialogueData::read(&path).unwrap().files[0].key_byte,
+            Some(0)
+        );
+
+        let out = dir.join("untranslated");
+        write_from_data(&dd, &out, &WriteOptions::default()).unwrap();
+        let written = std::fs::read(out.join("Test.tsc")).unwrap();
+        assert_eq!(written, raw);
+        assert_eq!(key_change(&dd.files[0], &written, None), None);
+        let again = dump_script(PathBuf::from("Test.tsc"), written, &options)
+            .unwrap()
+            .unwrap();
+        assert_eq!(again.key_byte, Some(0));
+
+        dd.files[0].dialogues[0][0].text[0].0 = "Bonjour !".to_string();
+        let out = dir.join("translated");
+        write_from_data(&dd, &out, &WriteOptions::default()).unwrap();
+        let written = std::fs::read(out.join("Test.tsc")).unwrap();
+        let (old, new) = key_change(&dd.files[0], &written, None).unwrap();
+        assert_eq!(old, 0);
+        assert_eq!(new, written[written.len() / 2]);
+        assert_ne!(new, 0);
+    }
+
+    #[test]
+    fn formatting_is_detected() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let dd = dump_dir(&dir.join("data"), &[("Test.tsc", TWO_BOXES)]);
+        for pretty in [false, true] {
+            let path = dir.join(format!("texts-{pretty}.json"));
+            dd.save(&path, pretty).unwrap();
+            assert_eq!(DialogueData::is_pretty(&path), pretty);
+            let split = dir.join(format!("split-{pretty}"));
+            std::fs::create_dir_all(&split).unwrap();
+            dd.save(&split, pretty).unwrap();
+            assert_eq!(DialogueData::is_pretty(&split), pretty);
+        }
+        assert!(!DialogueData::is_pretty(&dir.join("missing.json")));
+    }
+}
@@ -1,364 +1,3233 @@
 use anyhow::{Result, anyhow};
-use glob::glob;
-use logos::Logos;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, PrintStyledContent, StyledContent, Stylize};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use doukutsu_extractor::{
+    CipherKey, DialogueData, DumpOptions, FileData, InvalidSegments, Layout, Speech, SpeechKind,
+    TextEncoding, WriteOptions, WriteSummary, control_codes, dump_contents, dump_scripts,
+    find_scripts, game_data_root, is_credits, is_zip, read_charset, sha256_hex, translation_schema,
+    unknown_tokens, visible_len, write_from_data, zip_scripts,
+};
+use glob::Pattern;
+use log::{Level, LevelFilter, warn};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::{Reader, XmlVersion};
 use serde::{Deserialize, Serialize};
-use std::io::{BufReader, Write};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{IsTerminal, Write};
 use std::ops::Range;
-use std::path::PathBuf;
-
-#[derive(Default, Debug, Clone, PartialEq)]
-pub enum LexingError {
-    #[default]
-    Other,
-}
-
-#[derive(Logos, Debug, Clone, PartialEq)]
-#[logos(skip r"\r\n")]
-pub enum Token {
-    // #[token("\r\n")]
-    // Newline,
-    #[regex(r"<[A-Z\+\-\d]{3}", priority = 6)]
-    LbCode,
-    #[regex(r"\d{3,4}", priority = 7)]
-    NumCode,
-    #[token("<FAC0000")]
-    NormalWidth,
-    #[token("<FAC0001")]
-    SueSmile,
-    #[token("<FAC0002")]
-    SueFrown,
-    #[token("<FAC0003")]
-    SueAngry,
-    #[token("<FAC0004")]
-    SueHurt,
-    #[token("<FAC0005")]
-    BalrogNormal,
-    #[token("<FAC0006")]
-    TorokoNormal,
-    #[token("<FAC0007")]
-    King,
-    #[token("<FAC0008")]
-    TorokoAngry,
-    #[token("<FAC0009")]
-    Jack,
-    #[token("<FAC0010")]
-    Kazuma,
-    #[token("<FAC0011")]
-    TorokoRage,
-    #[token("<FAC0012")]
-    Igor,
-    #[token("<FAC0013")]
-    Jenka,
-    #[token("<FAC0014")]
-    BalrogSmile,
-    #[token("<FAC0015")]
-    MiseryNormal,
-    #[token("<FAC0016")]
-    MiserySmile,
-    #[token("<FAC0017")]
-    BoosterHurt,
-    #[token("<FAC0018")]
-    BoosterNormal,
-    #[token("<FAC0019")]
-    CurlySmile,
-    #[token("<FAC0020")]
-    CurlyFrown,
-    #[token("<FAC0021")]
-    Doctor,
-    #[token("<FAC0022")]
-    Momorin,
-    #[token("<FAC0023")]
-    BalrogHurt,
-    #[token("<FAC0024")]
-    BrokenRobot,
-    #[token("<FAC0025")]
-    CurlyUnknown,
-    #[token("<FAC0026")]
-    MiseryAngry,
-    #[token("<FAC0027")]
-    HumanSue,
-    #[token("<FAC0028")]
-    Itoh,
-    #[token("<FAC0029")]
-    Ballos,
-    #[token("<MSG")]
-    Message,
-    #[token("<NOD")]
-    Nod,
-    #[token("<CLR")]
-    Clear,
-    #[token("<END")]
-    End,
-    #[token("#")]
-    Pound,
-    #[token(":")]
-    Colon,
-    #[regex(r#"[\d]{3}|[\-a-zA-Z.\!?=\*'" ][a-zA-Z,.!?;\d\+\-\'"= \*\r\n]*(?:<NUM0000)?"#, |lex| lex.slice().to_owned())]
-    Text(String),
-    #[regex(r".", priority=1, callback = |lex| lex.slice().to_owned())]
-    Other(String),
-}
-
-impl Token {
-    pub fn is_face(&self) -> bool {
-        matches!(
-            self,
-            Token::NormalWidth
-                | Token::SueSmile
-                | Token::SueFrown
-                | Token::SueAngry
-                | Token::SueHurt
-                | Token::BalrogNormal
-                | Token::TorokoNormal
-                | Token::King
-                | Token::TorokoAngry
-                | Token::Jack
-                | Token::Kazuma
-                | Token::TorokoRage
-                | Token::Igor
-                | Token::Jenka
-                | Token::BalrogSmile
-                | Token::MiseryNormal
-                | Token::MiserySmile
-                | Token::BoosterHurt
-                | Token::BoosterNormal
-                | Token::CurlySmile
-                | Token::CurlyFrown
-                | Token::Doctor
-                | Token::Momorin
-                | Token::BalrogHurt
-                | Token::BrokenRobot
-                | Token::CurlyUnknown
-                | Token::MiseryAngry
-                | Token::HumanSue
-                | Token::Itoh
-                | Token::Ballos
-        )
-    }
-}
-
-pub fn tsc_decode(b: Vec<u8>) -> Vec<u8> {
-    let enc_idx = b.len() / 2;
-    let enc = b[enc_idx];
-    b.iter()
-        .enumerate()
-        .map(|(i, c)| match i == enc_idx {
-            false => c.wrapping_sub(enc),
-            true => *c,
-        })
-        .collect()
-}
-
-pub fn tsc_encode(s: String) -> Vec<u8> {
-    let b: Vec<u8> = s.into();
-    let enc_idx = b.len() / 2;
-    let enc = b[enc_idx];
-    b.iter()
-        .enumerate()
-        .map(|(i, c)| match i == enc_idx {
-            false => c.wrapping_add(enc),
-            true => *c,
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+#[derive(Debug, Default)]
+struct AppArgs {
+    game_data: Option<PathBuf>,
+    translation_file: Option<PathBuf>,
+    split: Option<PathBuf>,
+    /// Every `--translation_file` given, for the commands taking several.
+    translation_files: Vec<PathBuf>,
+    base_translation: Option<PathBuf>,
+    output_dir: Option<PathBuf>,
+    /// Every `--output_dir` given, one per translation file for “write”.
+    output_dirs: Vec<PathBuf>,
+    po_file: Option<PathBuf>,
+    csv_file: Option<PathBuf>,
+    xliff_file: Option<PathBuf>,
+    script_file: Option<PathBuf>,
+    charset: Option<PathBuf>,
+    checkpoint: Option<PathBuf>,
+    encoding: TextEncoding,
+    merge: bool,
+    max_width: usize,
+    wrap: Option<usize>,
+    strict: bool,
+    jobs: Option<usize>,
+    pretty: bool,
+    names: Option<PathBuf>,
+    faces: Option<PathBuf>,
+    context: Option<PathBuf>,
+    quiet: bool,
+    force: bool,
+    no_decrypt: bool,
+    key: Option<CipherKey>,
+    files: Option<Pattern>,
+    pattern: Option<Pattern>,
+    max_depth: Option<usize>,
+    untranslated: bool,
+    threshold: usize,
+    layout: Option<Layout>,
+    backup: bool,
+    normalize_case: bool,
+    force_extension: bool,
+    warn_whitespace: bool,
+    dedupe_report: bool,
+    propagate: bool,
+    manifest: Option<PathBuf>,
+    inline_codes: bool,
+    nod_breaks: bool,
+    escape_bytes: bool,
+    check: bool,
+    report_unknown: bool,
+    original: bool,
+    include_empty: bool,
+    only_changed: bool,
+    allow_inplace: bool,
+    filter_character: Option<String>,
+    kind: SpeechKind,
+    sample: Option<usize>,
+}
+
+impl AppArgs {
+    /// Whether to show progress bars, which only make sense in a terminal.
+    fn progress(&self) -> bool {
+        !self.quiet && std::io::stdout().is_terminal()
+    }
+
+    fn dump_options(&self) -> Result<DumpOptions> {
+        Ok(DumpOptions {
+            encoding: self.encoding,
+            strict: self.strict,
+            jobs: self.jobs,
+            names: match &self.names {
+                Some(path) => toml::from_str(&std::fs::read_to_string(path)?)?,
+                None => BTreeMap::new(),
+            },
+            faces: match &self.faces {
+                Some(path) => toml::from_str(&std::fs::read_to_string(path)?)?,
+                None => BTreeMap::new(),
+            },
+            context: match &self.context {
+                Some(path) => toml::from_str(&std::fs::read_to_string(path)?)?,
+                None => BTreeMap::new(),
+            },
+            plaintext: self.no_decrypt,
+            key: self.key,
+            layout: self.layout,
+            pattern: self.pattern.as_ref().map(|p| p.as_str().to_string()),
+            max_depth: self.max_depth,
+            inline_codes: self.inline_codes,
+            nod_breaks: self.nod_breaks,
+            escape_bytes: self.escape_bytes,
+            progress: self.progress(),
+            include_empty: self.include_empty,
         })
-        .collect()
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct Speech {
-    character: String,
-    text: Vec<(String, Range<usize>)>,
+fn dump(data_dir: PathBuf, output: PathBuf, args: &AppArgs) -> Result<()> {
+    // with `-`, stdout holds nothing but the JSON, so it can be piped along
+    let to_stdout = output.as_os_str() == "-";
+    // there's no existing translation file to keep the translations of
+    if args.merge && to_stdout {
+        return Err(usage(
+            "`--merge` needs a translation file to merge into, not stdout (`-`)",
+        ));
+    }
+    let options = args.dump_options()?;
+    let root = game_data_root(&data_dir);
+    let selected = |p: &Path| {
+        args.files
+            .as_ref()
+            .is_none_or(|pattern| pattern.matches_path(p.strip_prefix(&root).unwrap_or(p)))
+    };
+    let (scanned, mut dialogue) = match is_zip(&data_dir) {
+        true => {
+            let mut scripts = zip_scripts(&data_dir, &options)?;
+            scripts.retain(|(p, _)| selected(p));
+            (
+                scripts.len(),
+                dump_contents(root.clone(), scripts, &options)?,
+            )
+        }
+        false => {
+            let mut paths = find_scripts(&data_dir, &options)?;
+            paths.retain(|p| selected(p));
+            (paths.len(), dump_scripts(root.clone(), paths, &options)?)
+        }
+    };
+    if let Some(name) = &args.filter_character {
+        // the translations of everyone else would be merged away
+        if args.merge {
+            return Err(usage("`--filter-character` can't be used with `--merge`"));
+        }
+        let kind = match name.eq_ignore_ascii_case("narration") {
+            true => SpeechKind::Narration,
+            false => SpeechKind::Speech,
+        };
+        if ![SpeechKind::All, kind].contains(&args.kind) {
+            return Err(usage(format!(
+                "`--filter-character {name}` and `--kind` contradict each other"
+            )));
+        }
+        dialogue.retain_speeches(|speech| speech.speaker().eq_ignore_ascii_case(name));
+    }
+    if args.kind != SpeechKind::All {
+        if args.merge {
+            return Err(usage("`--kind` can't be used with `--merge`"));
+        }
+        dialogue.retain_speeches(|speech| args.kind.matches(speech));
+    }
+    if let Some(n) = args.sample {
+        // everything past the sample would be merged away
+        if args.merge {
+            return Err(usage("`--sample` can't be used with `--merge`"));
+        }
+        dialogue.keep_sample(n);
+    }
+    let stats = (!args.quiet && !to_stdout).then(|| dialogue.stats(scanned));
+    if args.report_unknown && !to_stdout {
+        report_unknown(&dialogue);
+    }
+
+    if args.merge && output.exists() {
+        let mut old = DialogueData::read(&output)?;
+        // files outside of `--files` weren't re-extracted, so they're kept
+        // exactly as they were
+        let mut untouched = vec![];
+        if let Some(pattern) = &args.files {
+            let (matched, rest) = old
+                .files
+                .into_iter()
+                .partition(|fd| pattern.matches_path(&fd.path));
+            old.files = matched;
+            untouched = rest;
+        }
+        let summary = dialogue.merge_translations(old);
+        dialogue.files.extend(untouched);
+        dialogue.sort_files();
+        println!(
+            "Merged with {output:?}: {} kept, {} added, {} removed",
+            summary.kept, summary.added, summary.removed
+        );
+    }
+
+    // the transcripts follow the translation file, merged translations and
+    // all, but nothing reads them back
+    if let Some(dir) = &args.output_dir {
+        write_transcripts(&dialogue, dir)?;
+        if !to_stdout {
+            println!("Wrote the transcripts to {dir:?}");
+        }
+    }
+
+    if to_stdout {
+        let mut stdout = std::io::stdout().lock();
+        dialogue.save_to(&mut stdout, args.pretty)?;
+        writeln!(stdout)?;
+        return Ok(());
+    }
+    // merging updates the file in place, so it stays the way it was
+    let pretty = args.pretty || (args.merge && DialogueData::is_pretty(&output));
+    match args.split.is_some() {
+        true => {
+            std::fs::create_dir_all(&output)?;
+            dialogue.save_split(&output, pretty)?;
+        }
+        false => dialogue.save(&output, pretty)?,
+    }
+    if let Some(stats) = stats {
+        print!("{stats}");
+    }
+    if args.dedupe_report {
+        dedupe_report(&dialogue);
+    }
+    Ok(())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct FileData {
-    dialogues: Vec<Vec<Speech>>,
-    original: String,
+/// Prints how often each token the lexer didn't recognize shows up in each
+/// file, with the first few lines it's found on.
+fn report_unknown(dd: &DialogueData) {
+    // the credits aren't lexed at all
+    for fd in dd.files.iter().filter(|fd| !is_credits(&fd.path)) {
+        let mut found: BTreeMap<String, Vec<Range<usize>>> = BTreeMap::new();
+        for (token, range) in unknown_tokens(&fd.original) {
+            found.entry(token).or_default().push(range);
+        }
+        if found.is_empty() {
+            continue;
+        }
+        println!("Unknown tokens in {:?}:", fd.path);
+        for (token, ranges) in &found {
+            println!("  {token:?}: {}x", ranges.len());
+            for range in ranges.iter().take(3) {
+                let line = fd.original[..range.start].matches('\n').count() + 1;
+                let start = fd.original[..range.start].rfind('\n').map_or(0, |i| i + 1);
+                let end = fd.original[range.end..]
+                    .find(['\r', '\n'])
+                    .map_or(fd.original.len(), |i| range.end + i);
+                println!("    line {line}: {:?}", &fd.original[start..end]);
+            }
+        }
+    }
+}
+
+/// Prints the speeches whose original text appears more than once, most
+/// repeated first.
+fn dedupe_report(dd: &DialogueData) {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for fd in &dd.files {
+        for speech in fd.dialogues.iter().flatten() {
+            let original: String = speech
+                .text
+                .iter()
+                .map(|(_, range)| fd.original_text(range, dd.encoding))
+                .collect();
+            *counts.entry(original).or_default() += 1;
+        }
+    }
+    let mut repeated: Vec<_> = counts.into_iter().filter(|(_, n)| *n > 1).collect();
+    repeated.sort_by(|(a, n), (b, m)| m.cmp(n).then(a.cmp(b)));
+    println!(
+        "{} lines are repeated, making up {} speeches",
+        repeated.len(),
+        repeated.iter().map(|(_, n)| n).sum::<usize>()
+    );
+    for (original, n) in repeated.iter().take(20) {
+        println!("  {n}x {original:?}");
+    }
+    if repeated.len() > 20 {
+        println!("  ...");
+    }
+}
+
+/// Warns about every translated segment whose leading or trailing whitespace
+/// differs from the original's, which would shift the text in the box.
+fn warn_whitespace(dd: &DialogueData) {
+    let edges = |s: &str| {
+        let start = s.len() - s.trim_start().len();
+        let end = s.trim_end().len().max(start);
+        (s[..start].to_string(), s[end..].to_string())
+    };
+    for fd in &dd.files {
+        let rel = &fd.path;
+        for (d, dialogue) in fd.dialogues.iter().enumerate() {
+            for (s, speech) in dialogue.iter().enumerate() {
+                for (k, (text, range)) in speech.text.iter().enumerate() {
+                    let original = fd.original_text(range, dd.encoding);
+                    if *text == original {
+                        continue;
+                    }
+                    let ((old_lead, old_trail), (lead, trail)) = (edges(&original), edges(text));
+                    if old_lead != lead {
+                        warn!(
+                            "{}: dialogue {d}, speech {s}, segment {k}: leading whitespace changed from {old_lead:?} to {lead:?}",
+                            rel.display()
+                        );
+                    }
+                    if old_trail != trail {
+                        warn!(
+                            "{}: dialogue {d}, speech {s}, segment {k}: trailing whitespace changed from {old_trail:?} to {trail:?}",
+                            rel.display()
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// `path` with symlinks and `..` resolved, as far as it exists.
+fn resolve(path: &Path) -> PathBuf {
+    // the root of a script dumped on its own by a relative path is empty
+    let path = match path.as_os_str().is_empty() {
+        true => Path::new("."),
+        false => path,
+    };
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    for base in path.ancestors() {
+        if let Ok(resolved) = base.canonicalize() {
+            return resolved.join(path.strip_prefix(base).unwrap_or(Path::new("")));
+        }
+    }
+    path
+}
+
+fn write(
+    translation_file: PathBuf,
+    output_dir: PathBuf,
+    options: &WriteOptions,
+    check_whitespace: bool,
+    allow_inplace: bool,
+    base: Option<&HashMap<SegmentKey, String>>,
+    charset: Option<&HashSet<char>>,
+) -> Result<WriteSummary> {
+    let mut dd = DialogueData::read(&translation_file)?;
+    if dd.sample {
+        return Err(usage(format!(
+            "{translation_file:?} is only a sample of the dialogue, written by `dump --sample`; dump the whole game to write it"
+        )));
+    }
+    // writing over the scripts the translation was dumped from is almost
+    // always a mistake, and can't be undone without --backup
+    if !allow_inplace
+        && !options.check
+        && resolve(&output_dir).starts_with(resolve(&dd.game_data_root))
+    {
+        return Err(usage(format!(
+            "{output_dir:?} is the game data folder {:?} that {translation_file:?} was dumped from, or inside it; pass --allow-inplace to overwrite its scripts",
+            dd.game_data_root
+        )));
+    }
+    if let Some(base) = base {
+        let filled = fall_back_to(&mut dd, base);
+        println!(
+            "{translation_file:?}: {filled} untranslated speeches taken from the base translation"
+        );
+    }
+    if check_whitespace {
+        warn_whitespace(&dd);
+    }
+    // a last look at what's about to go into the game, since a character it
+    // can't show is only noticed when playing
+    match unrenderable(&dd, charset) {
+        0 => {}
+        n => {
+            return Err(Failure::Validation(format!(
+                "{translation_file:?} has {n} characters the game can't render; nothing was written"
+            ))
+            .into());
+        }
+    }
+    let summary = write_from_data(&dd, &output_dir, options)?;
+    for p in &summary.backups {
+        println!("Backed up to {p:?}");
+    }
+    let (wrote, written) = match options.check {
+        true => ("Would write", "would be written"),
+        false => ("Wrote", "written"),
+    };
+    for p in &summary.written {
+        println!("{wrote} {p:?}");
+    }
+    let unchanged = match options.only_changed {
+        true => format!(", {} left out as untranslated", summary.unchanged.len()),
+        false => String::new(),
+    };
+    println!(
+        "{translation_file:?}: {} files {written}, {} already up to date{unchanged}",
+        summary.written.len(),
+        summary.skipped.len()
+    );
+    if !summary.failed.is_empty() {
+        println!(
+            "Skipped {} files that couldn't be written:",
+            summary.failed.len()
+        );
+        for (p, _) in &summary.failed {
+            println!("  {p:?}");
+        }
+    }
+    Ok(summary)
+}
+
+/// The error for the files of `summaries` that couldn't be written, if any:
+/// one the exit code shows as an I/O failure if any of theirs was one, and
+/// as a failed check otherwise.
+fn write_failures(summaries: &[WriteSummary]) -> Result<()> {
+    let failed: Vec<_> = summaries.iter().flat_map(|s| &s.failed).collect();
+    if failed.is_empty() {
+        return Ok(());
+    }
+    let message = format!("{} files couldn't be written", failed.len());
+    match failed.iter().any(|(_, e)| exit_code(e) == 3) {
+        true => Err(std::io::Error::other(message).into()),
+        false => Err(Failure::Validation(message).into()),
+    }
+}
+
+/// One output file listed in the manifest written by `write --manifest`.
+#[derive(Debug, Serialize)]
+struct ManifestEntry {
     path: PathBuf,
+    bytes: usize,
+    sha256: String,
+    /// Whether `write` left the file alone because it was already up to date.
+    unchanged: bool,
 }
 
+fn write_manifest(manifest: &PathBuf, summaries: &[WriteSummary]) -> Result<()> {
+    let mut entries = vec![];
+    for summary in summaries {
+        let files = (summary.written.iter().map(|p| (p, false)))
+            .chain(summary.skipped.iter().map(|p| (p, true)));
+        for (path, unchanged) in files {
+            let contents = std::fs::read(path)?;
+            entries.push(ManifestEntry {
+                path: path.clone(),
+                bytes: contents.len(),
+                sha256: sha256_hex(&contents),
+                unchanged,
+            });
+        }
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    std::fs::write(manifest, serde_json::to_string_pretty(&entries)?)?;
+    println!("Wrote {manifest:?}");
+    Ok(())
+}
+
+fn po_escape(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '\\' => out += "\\\\",
+            '"' => out += "\\\"",
+            '\n' => out += "\\n",
+            '\r' => out += "\\r",
+            '\t' => out += "\\t",
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Parses a quoted PO string such as `"Hello,\n"`.
+fn po_unescape(s: &str) -> Result<String> {
+    let inner = s
+        .trim()
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .ok_or(anyhow!("expected a quoted string, got `{s}`"))?;
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some(c @ ('\\' | '"')) => out.push(c),
+            c => return Err(anyhow!("invalid escape sequence `\\{}`", c.unwrap_or(' '))),
+        }
+    }
+    Ok(out)
+}
+
+#[derive(Debug, Default)]
+struct ImportOptions {
+    pretty: bool,
+    /// Also fill in untranslated segments whose original text was translated
+    /// elsewhere.
+    propagate: bool,
+}
+
+/// Saves the translation file after importing `imported` translations into
+/// it.
+fn save_import(
+    mut dd: DialogueData,
+    translation_file: &PathBuf,
+    imported: usize,
+    options: &ImportOptions,
+) -> Result<()> {
+    let propagated = match options.propagate {
+        true => dd.propagate_translations(),
+        false => 0,
+    };
+    // keep the file the way it was, unless asked to indent it
+    let pretty = options.pretty || DialogueData::is_pretty(translation_file);
+    dd.save(translation_file, pretty)?;
+    println!("Imported {imported} translations into {translation_file:?}");
+    if options.propagate {
+        println!("Filled in {propagated} repeated lines from their translations");
+    }
+    Ok(())
+}
+
+/// Writes one PO entry per text segment. The `msgctxt` identifies the segment
+/// as `path:dialogue:speech:segment`, keeping identical lines apart.
+fn export_po(translation_file: PathBuf, po_file: PathBuf) -> Result<()> {
+    let dd = DialogueData::read(&translation_file)?;
+    let mut po =
+        String::from("msgid \"\"\nmsgstr \"\"\n\"Content-Type: text/plain; charset=UTF-8\\n\"\n");
+    for fd in &dd.files {
+        let rel = &fd.path;
+        for (d, dialogue) in fd.dialogues.iter().enumerate() {
+            for (s, speech) in dialogue.iter().enumerate() {
+                for (k, (text, range)) in speech.text.iter().enumerate() {
+                    let original = fd.original_text(range, dd.encoding);
+                    let translation = if *text == original { "" } else { text.as_str() };
+                    let event = speech
+                        .event
+                        .map(|e| format!(", event #{e:04}"))
+                        .unwrap_or_default();
+                    po += &format!(
+                        "\n#. {}{event}, dialogue {d}, speech {s} ({})\nmsgctxt \"{}:{d}:{s}:{k}\"\nmsgid \"{}\"\nmsgstr \"{}\"\n",
+                        rel.display(),
+                        speech.speaker(),
+                        po_escape(&rel.display().to_string()),
+                        po_escape(&original),
+                        po_escape(translation),
+                    );
+                }
+            }
+        }
+    }
+    std::fs::write(&po_file, po)?;
+    println!("Wrote {po_file:?}");
+    Ok(())
+}
+
+/// Reads the translations from a PO file written by [`export_po`] back into
+/// the translation file. Entries with an empty `msgstr` revert to the original
+/// text.
+fn import_po(translation_file: PathBuf, po_file: PathBuf, options: &ImportOptions) -> Result<()> {
+    let mut dd = DialogueData::read(&translation_file)?;
+    let po = std::fs::read_to_string(&po_file)?;
+
+    let mut entries: HashMap<String, String> = HashMap::new();
+    let (mut ctxt, mut msgstr) = (None::<String>, None::<String>);
+    // which of the two fields continuation lines belong to, if any
+    let mut current: Option<&mut String> = None;
+    for (n, line) in po.lines().enumerate() {
+        let line = line.trim();
+        let parse = |s: &str| {
+            po_unescape(s).map_err(|e| Failure::Parse(format!("{po_file:?}:{}: {e}", n + 1)))
+        };
+        if let Some(rest) = line.strip_prefix("msgctxt ") {
+            if let (Some(c), Some(m)) = (ctxt.take(), msgstr.take()) {
+                entries.insert(c, m);
+            }
+            ctxt = Some(parse(rest)?);
+            current = ctxt.as_mut();
+        } else if line.starts_with("msgid ") {
+            current = None;
+        } else if let Some(rest) = line.strip_prefix("msgstr ") {
+            msgstr = Some(parse(rest)?);
+            current = msgstr.as_mut();
+        } else if line.starts_with('"') {
+            if let Some(field) = current.as_mut() {
+                **field += &parse(line)?;
+            }
+        } else {
+            current = None;
+        }
+    }
+    if let (Some(c), Some(m)) = (ctxt, msgstr) {
+        entries.insert(c, m);
+    }
+
+    let mut imported = 0;
+    for fd in dd.files.iter_mut() {
+        let rel = fd.path.to_owned();
+        let mut dialogues = std::mem::take(&mut fd.dialogues);
+        for (d, dialogue) in dialogues.iter_mut().enumerate() {
+            for (s, speech) in dialogue.iter_mut().enumerate() {
+                for (k, (text, range)) in speech.text.iter_mut().enumerate() {
+                    let Some(msgstr) = entries.remove(&format!("{}:{d}:{s}:{k}", rel.display()))
+                    else {
+                        continue;
+                    };
+                    *text = match msgstr.is_empty() {
+                        true => fd.original_text(range, dd.encoding).into_owned(),
+                        false => msgstr,
+                    };
+                    imported += 1;
+                }
+            }
+        }
+        fd.dialogues = dialogues;
+    }
+    for ctxt in entries.keys() {
+        warn!("Ignoring PO entry `{ctxt}`: no such entry in {translation_file:?}");
+    }
+
+    save_import(dd, &translation_file, imported, options)
+}
+
+/// One text segment in the spreadsheet written by [`export_csv`].
 #[derive(Debug, Serialize, Deserialize)]
-struct DialogueData {
-    game_data_root: PathBuf,
-    files: Vec<FileData>,
+struct CsvRow {
+    path: String,
+    dialogue: usize,
+    speech: usize,
+    segment: usize,
+    /// Left empty for narration.
+    character: String,
+    original: String,
+    /// Left empty while untranslated.
+    translation: String,
 }
 
-impl FileData {
-    pub fn reconstruct(&self) -> String {
-        let mut str = String::new();
-        let mut last_range_end = 0;
-        for speech in self.dialogues.iter().flatten() {
-            for (text, range) in &speech.text {
-                str += &self.original[last_range_end..range.start];
-                str += text;
-                last_range_end = range.end;
+fn export_csv(translation_file: PathBuf, csv_file: PathBuf) -> Result<()> {
+    let dd = DialogueData::read(&translation_file)?;
+    let mut writer = csv::Writer::from_path(&csv_file)?;
+    for fd in &dd.files {
+        let rel = &fd.path;
+        for (d, dialogue) in fd.dialogues.iter().enumerate() {
+            for (s, speech) in dialogue.iter().enumerate() {
+                for (k, (text, range)) in speech.text.iter().enumerate() {
+                    let original = fd.original_text(range, dd.encoding);
+                    writer.serialize(CsvRow {
+                        path: rel.display().to_string(),
+                        dialogue: d,
+                        speech: s,
+                        segment: k,
+                        character: speech.character.clone().unwrap_or_default(),
+                        translation: match *text == original {
+                            true => String::new(),
+                            false => text.clone(),
+                        },
+                        original: original.into_owned(),
+                    })?;
+                }
             }
         }
-        str += &self.original[last_range_end..self.original.len()];
-        str
     }
+    writer.flush()?;
+    println!("Wrote {csv_file:?}");
+    Ok(())
 }
 
-fn dialogues_from_tsc(text: &str) -> Vec<Vec<Speech>> {
-    let mut lex = Token::lexer(text);
-    let mut character = String::new();
-    let mut speech: Vec<(String, Range<usize>)> = vec![];
-    let mut dialogue: Vec<Speech> = vec![];
-    let mut dialogues: Vec<Vec<Speech>> = vec![];
-    while let Some(Ok(token)) = lex.next() {
-        if matches!(token, Token::Message) {
-            if !speech.is_empty() {
-                dialogue.push(Speech {
-                    character: character.clone(),
-                    text: speech.clone(),
-                });
+/// Reads the translations from a spreadsheet written by [`export_csv`] back
+/// into the translation file. Rows with an empty translation revert to the
+/// original text.
+fn import_csv(translation_file: PathBuf, csv_file: PathBuf, options: &ImportOptions) -> Result<()> {
+    let mut dd = DialogueData::read(&translation_file)?;
+    let mut reader = csv::Reader::from_path(&csv_file)?;
+    let mut imported = 0;
+    for row in reader.deserialize() {
+        let row: CsvRow = row?;
+        let Some(fd) = dd
+            .files
+            .iter_mut()
+            .find(|fd| fd.path == std::path::Path::new(&row.path))
+        else {
+            warn!(
+                "Ignoring row for {:?}: no such file in {translation_file:?}",
+                row.path
+            );
+            continue;
+        };
+        let mut dialogues = std::mem::take(&mut fd.dialogues);
+        match dialogues
+            .get_mut(row.dialogue)
+            .and_then(|dialogue| dialogue.get_mut(row.speech))
+            .and_then(|speech| speech.text.get_mut(row.segment))
+        {
+            Some((text, range)) => {
+                *text = match row.translation.is_empty() {
+                    true => fd.original_text(range, dd.encoding).into_owned(),
+                    false => row.translation,
+                };
+                imported += 1;
             }
-            if !dialogue.is_empty() {
-                dialogues.push(dialogue.clone());
+            None => warn!(
+                "Ignoring row for {}:{}:{}:{}: no such entry in {translation_file:?}",
+                row.path, row.dialogue, row.speech, row.segment
+            ),
+        }
+        fd.dialogues = dialogues;
+    }
+
+    save_import(dd, &translation_file, imported, options)
+}
+
+/// Escapes `s` for XML text or attribute values. Carriage returns are written
+/// as character references, since XML parsers would otherwise drop them.
+fn xml_escape(s: &str) -> String {
+    quick_xml::escape::escape(s).replace('\r', "&#13;")
+}
+
+/// Splits `text` into runs of plain text and TSC control codes, such as
+/// `<NUM0000`, marking the latter with `true`.
+fn split_codes(text: &str) -> Vec<(bool, &str)> {
+    let mut runs = vec![];
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        let code = &rest[start..];
+        let is_code = code.len() >= 4
+            && code[1..4]
+                .bytes()
+                .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit() || b == b'+' || b == b'-');
+        if !is_code {
+            runs.push((false, &rest[..start + 1]));
+            rest = &rest[start + 1..];
+            continue;
+        }
+        let len = 4 + code[4..]
+            .find(|c: char| !(c.is_ascii_digit() || c == ':'))
+            .unwrap_or(code.len() - 4);
+        runs.push((false, &rest[..start]));
+        runs.push((true, &code[..len]));
+        rest = &code[len..];
+    }
+    runs.push((false, rest));
+    runs.retain(|(_, run)| !run.is_empty());
+    runs
+}
+
+/// Writes `runs` of text as XLIFF inline content, turning control codes into
+/// `<ph>` placeholders that refer to `<data>` entries in `data`.
+fn xliff_inline(runs: &[(bool, &str)], prefix: char, data: &mut Vec<(String, String)>) -> String {
+    let mut out = String::new();
+    for (is_code, run) in runs {
+        match is_code {
+            true => {
+                let id = format!("{prefix}{}", data.len() + 1);
+                out += &format!("<ph id=\"{id}\" dataRef=\"{id}\"/>");
+                data.push((id, run.to_string()));
             }
-            dialogue.clear();
-            speech.clear();
+            false => out += &xml_escape(run),
         }
-        if matches!(token, Token::Message | Token::NormalWidth) {
-            character = "NP".to_string();
+    }
+    out
+}
+
+/// Writes one XLIFF 2.0 `<unit>` per speech, grouped into a `<file>` per
+/// script. The control codes between the text segments of a speech become
+/// `<ph>` placeholders with ids starting with `b`, and those within the text
+/// ids starting with `c` (or `t` in the translation).
+fn export_xliff(translation_file: PathBuf, xliff_file: PathBuf) -> Result<()> {
+    let dd = DialogueData::read(&translation_file)?;
+    let src_lang = match dd.encoding {
+        TextEncoding::Utf8 => "en",
+        TextEncoding::ShiftJis => "ja",
+    };
+    let mut xliff = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<xliff xmlns=\"urn:oasis:names:tc:xliff:document:2.0\" version=\"2.0\" srcLang=\"{src_lang}\" trgLang=\"und\">\n"
+    );
+    for (f, fd) in dd.files.iter().enumerate() {
+        // XLIFF doesn't allow a `<file>` without any units
+        if fd.dialogues.is_empty() {
+            continue;
         }
-        if token.is_face() {
-            if !speech.is_empty() {
-                // println!("{:?}\n{}", &speech, &text[span_start..span_end]);
-                dialogue.push(Speech {
-                    character: character.clone(),
-                    text: speech.clone(),
-                });
+        let rel = &fd.path;
+        xliff += &format!(
+            "  <file id=\"f{}\" original=\"{}\">\n",
+            f + 1,
+            xml_escape(&rel.display().to_string())
+        );
+        for (d, dialogue) in fd.dialogues.iter().enumerate() {
+            for (s, speech) in dialogue.iter().enumerate() {
+                let (mut data, mut target_data) = (vec![], vec![]);
+                let (mut source, mut target) = (String::new(), String::new());
+                let mut translated = false;
+                let mut last_end = None;
+                for (text, range) in &speech.text {
+                    if let Some(end) = last_end {
+                        let codes = fd.original_text(&(end..range.start), dd.encoding);
+                        let id = format!("b{}", data.len() + 1);
+                        let ph = format!("<ph id=\"{id}\" dataRef=\"{id}\"/>");
+                        source += &ph;
+                        target += &ph;
+                        data.push((id, codes.into_owned()));
+                    }
+                    let original = fd.original_text(range, dd.encoding);
+                    translated |= *text != original;
+                    source += &xliff_inline(&split_codes(&original), 'c', &mut data);
+                    target += &xliff_inline(&split_codes(text), 't', &mut target_data);
+                    last_end = Some(range.end);
+                }
+                // the target is only written out once translated
+                if translated {
+                    data.extend(target_data);
+                }
+                xliff += &format!(
+                    "    <unit id=\"d{d}-s{s}\">\n      <notes>\n        <note category=\"character\">{}</note>\n",
+                    xml_escape(speech.speaker())
+                );
+                if let Some(event) = speech.event {
+                    xliff += &format!("        <note category=\"event\">{event:04}</note>\n");
+                }
+                xliff += "      </notes>\n";
+                if !data.is_empty() {
+                    xliff += "      <originalData>\n";
+                    for (id, code) in &data {
+                        xliff +=
+                            &format!("        <data id=\"{id}\">{}</data>\n", xml_escape(code));
+                    }
+                    xliff += "      </originalData>\n";
+                }
+                match translated {
+                    true => {
+                        xliff += &format!(
+                            "      <segment state=\"translated\">\n        <source xml:space=\"preserve\">{source}</source>\n        <target xml:space=\"preserve\">{target}</target>\n      </segment>\n"
+                        )
+                    }
+                    false => {
+                        xliff += &format!(
+                            "      <segment state=\"initial\">\n        <source xml:space=\"preserve\">{source}</source>\n      </segment>\n"
+                        )
+                    }
+                }
+                xliff += "    </unit>\n";
             }
-            speech.clear();
-            character = format!("{token:?}");
-        } else if let Token::Text(s) = token {
-            speech.push((s, lex.span()));
         }
+        xliff += "  </file>\n";
     }
-    dialogues
+    xliff += "</xliff>\n";
+    std::fs::write(&xliff_file, xliff)?;
+    println!("Wrote {xliff_file:?}");
+    Ok(())
 }
 
-#[derive(Debug)]
-struct AppArgs {
-    game_data: Option<PathBuf>,
-    translation_file: Option<PathBuf>,
-    output_dir: Option<PathBuf>,
+/// Reads the `<target>`s of an XLIFF file written by [`export_xliff`] back
+/// into the translation file. Each target is split back into text segments at
+/// its `b` placeholders; an empty target reverts the speech to the original
+/// text.
+fn import_xliff(
+    translation_file: PathBuf,
+    xliff_file: PathBuf,
+    options: &ImportOptions,
+) -> Result<()> {
+    let mut dd = DialogueData::read(&translation_file)?;
+    let xml = std::fs::read_to_string(&xliff_file)?;
+    let mut reader = Reader::from_str(&xml);
+
+    // the target text segments of each unit, keyed by file and unit id
+    let mut targets: HashMap<(String, String), Vec<String>> = HashMap::new();
+    let (mut file, mut unit) = (String::new(), String::new());
+    let mut data: HashMap<String, String> = HashMap::new();
+    // the `<data>` entry or target being read, if any
+    let mut current_data: Option<(String, String)> = None;
+    let mut target: Option<Vec<String>> = None;
+    let attr = |e: &BytesStart, name: &str| -> Result<String> {
+        Ok(e.try_get_attribute(name)?
+            .map(|a| {
+                a.normalized_value(XmlVersion::Implicit1_0)
+                    .map(|v| v.into_owned())
+            })
+            .transpose()?
+            .unwrap_or_default())
+    };
+    loop {
+        let text = match reader.read_event()? {
+            Event::Start(e) => {
+                match e.local_name().as_ref() {
+                    "file" => file = attr(&e, "original")?,
+                    "unit" => {
+                        unit = attr(&e, "id")?;
+                        data.clear();
+                    }
+                    "data" => current_data = Some((attr(&e, "id")?, String::new())),
+                    "target" => target = Some(vec![String::new()]),
+                    _ => {}
+                }
+                continue;
+            }
+            Event::Empty(e) => {
+                match (e.local_name().as_ref(), target.as_mut()) {
+                    ("ph", Some(segments)) => {
+                        let id = attr(&e, "dataRef")?;
+                        match id.starts_with('b') {
+                            true => segments.push(String::new()),
+                            false => match data.get(&id) {
+                                Some(code) => segments.last_mut().unwrap().push_str(code),
+                                None => {
+                                    return Err(Failure::Parse(format!(
+                                        "{xliff_file:?}: unit {unit:?} refers to unknown data {id:?}"
+                                    ))
+                                    .into());
+                                }
+                            },
+                        }
+                    }
+                    ("target", _) => {
+                        targets.insert((file.clone(), unit.clone()), vec![]);
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            Event::End(e) => {
+                match e.local_name().as_ref() {
+                    "data" => {
+                        if let Some((id, code)) = current_data.take() {
+                            data.insert(id, code);
+                        }
+                    }
+                    "target" => {
+                        if let Some(segments) = target.take() {
+                            targets.insert((file.clone(), unit.clone()), segments);
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            Event::Text(e) => e.xml10_content().into_owned(),
+            Event::CData(e) => e.xml10_content().into_owned(),
+            Event::GeneralRef(e) => match e.resolve_char_ref()? {
+                Some(c) => c.to_string(),
+                None => quick_xml::escape::resolve_predefined_entity(&e.xml10_content())
+                    .ok_or_else(|| {
+                        Failure::Parse(format!(
+                            "{xliff_file:?}: unknown entity `&{};`",
+                            e.xml10_content()
+                        ))
+                    })?
+                    .to_string(),
+            },
+            Event::Eof => break,
+            _ => continue,
+        };
+        if let Some((_, code)) = current_data.as_mut() {
+            *code += &text;
+        } else if let Some(segments) = target.as_mut() {
+            *segments.last_mut().unwrap() += &text;
+        }
+    }
+
+    let mut imported = 0;
+    for fd in dd.files.iter_mut() {
+        let rel = fd.path.display().to_string();
+        let mut dialogues = std::mem::take(&mut fd.dialogues);
+        for (d, dialogue) in dialogues.iter_mut().enumerate() {
+            for (s, speech) in dialogue.iter_mut().enumerate() {
+                let Some(segments) = targets.remove(&(rel.clone(), format!("d{d}-s{s}"))) else {
+                    continue;
+                };
+                let empty = segments.iter().all(String::is_empty);
+                if !empty && segments.len() != speech.text.len() {
+                    warn!(
+                        "Ignoring unit d{d}-s{s} of {rel}: expected {} segments, got {}",
+                        speech.text.len(),
+                        segments.len()
+                    );
+                    continue;
+                }
+                for (k, (text, range)) in speech.text.iter_mut().enumerate() {
+                    *text = match empty {
+                        true => fd.original_text(range, dd.encoding).into_owned(),
+                        false => segments[k].clone(),
+                    };
+                }
+                imported += 1;
+            }
+        }
+        fd.dialogues = dialogues;
+    }
+    for (file, unit) in targets.keys() {
+        warn!("Ignoring XLIFF unit {unit} of {file}: no such entry in {translation_file:?}");
+    }
+
+    save_import(dd, &translation_file, imported, options)
 }
 
-fn dump(data_dir: PathBuf, output: PathBuf) -> Result<()> {
-    let mut files: Vec<FileData> = vec![];
-    let pattern = data_dir.join("**/*.tsc");
+/// Writes the dialogue out as a transcript to read through, one `Character:
+/// text` line per speech, with a blank line between dialogues. It's Markdown
+/// if `script_file` ends in `.md`, and plain text otherwise.
+fn export_script(
+    translation_file: PathBuf,
+    script_file: PathBuf,
+    original: bool,
+    names: &BTreeMap<String, String>,
+) -> Result<()> {
+    let dd = DialogueData::read(&translation_file)?;
+    let markdown = script_file
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("md"));
+    let mut script = String::new();
+    for fd in &dd.files {
+        let rel = &fd.path;
+        script += &match markdown {
+            true => format!("## {}\n\n", rel.display()),
+            false => format!("=== {} ===\n\n", rel.display()),
+        };
+        script += &transcript(&dd, fd, original, names, markdown);
+    }
+    std::fs::write(&script_file, script)?;
+    println!("Wrote {script_file:?}");
+    Ok(())
+}
 
-    for path in (glob(
-        pattern
-            .to_str()
-            .ok_or(anyhow!("couldn't stringify pattern"))?,
-    )?)
-    .flatten()
-    {
-        let bytes = tsc_decode(std::fs::read(&path)?);
-        let text = String::from_utf8_lossy(&bytes);
-        let dialogues = dialogues_from_tsc(&text);
-        if !dialogues.is_empty() {
-            let data = FileData {
-                dialogues,
-                original: text.to_string(),
-                path,
+/// A file's speeches as a screenplay, one `Character: text` line each and a
+/// blank line after each dialogue, or in Markdown if `markdown`.
+fn transcript(
+    dd: &DialogueData,
+    fd: &FileData,
+    original: bool,
+    names: &BTreeMap<String, String>,
+    markdown: bool,
+) -> String {
+    let mut script = String::new();
+    for dialogue in &fd.dialogues {
+        for speech in dialogue {
+            let text: String = speech
+                .text
+                .iter()
+                .map(|(text, range)| match original {
+                    true => fd.original_text(range, dd.encoding),
+                    false => Cow::Borrowed(text.as_str()),
+                })
+                .collect::<String>()
+                .split('\n')
+                .map(str::trim)
+                .collect::<Vec<_>>()
+                .join(" / ");
+            let character = speech.character.as_ref().map(|c| names.get(c).unwrap_or(c));
+            script += &match (markdown, character) {
+                // two trailing spaces keep Markdown from joining the lines
+                (true, Some(character)) => format!("**{character}**: {text}  \n"),
+                (true, None) => format!("*{text}*  \n"),
+                (false, Some(character)) => format!("{character}: {text}\n"),
+                (false, None) => format!("{text}\n"),
             };
-            files.push(data);
         }
+        script += "\n";
+    }
+    script
+}
+
+/// Writes the transcript of each file of `dd` into `dir`, at the script's
+/// path plus `.txt` (e.g. `Stage/Pens1.tsc.txt`), for a quick read of what
+/// was dumped.
+fn write_transcripts(dd: &DialogueData, dir: &Path) -> Result<()> {
+    for fd in &dd.files {
+        let mut name = fd.path.as_os_str().to_owned();
+        name.push(".txt");
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap_or(dir))?;
+        std::fs::write(&path, transcript(dd, fd, false, &BTreeMap::new(), false))?;
+    }
+    Ok(())
+}
+
+/// Every speech of a translation file in order, keyed by its file and id (or
+/// its dialogue and speech index if it has none), with its dialogue and
+/// speech index, character and text.
+type SpeechTexts = Vec<((PathBuf, String), (usize, usize, String, String))>;
+
+/// Prints the speeches whose text differs between the `old` and `new`
+/// translation files, along with those only found in one of them. Speeches
+/// are matched by id like [`DialogueData::merge_translations`] does, so that
+/// one added or removed doesn't make all of those after it look changed.
+fn diff(old: PathBuf, new: PathBuf) -> Result<()> {
+    let speeches = |path: &PathBuf| -> Result<SpeechTexts> {
+        let dd = DialogueData::read(path)?;
+        let mut speeches = vec![];
+        for fd in &dd.files {
+            let rel = &fd.path;
+            for (d, dialogue) in fd.dialogues.iter().enumerate() {
+                for (s, speech) in dialogue.iter().enumerate() {
+                    let id = speech.id.clone().unwrap_or_else(|| format!("{d}:{s}"));
+                    let text = speech.text.iter().map(|(text, _)| text.as_str()).collect();
+                    speeches.push((
+                        (rel.to_owned(), id),
+                        (d, s, speech.speaker().to_string(), text),
+                    ));
+                }
+            }
+        }
+        Ok(speeches)
+    };
+    let old = speeches(&old)?;
+    let new = speeches(&new)?;
+    let mut added: HashMap<_, _> = new.iter().map(|(key, speech)| (key, speech)).collect();
+    let (mut changed, mut removed) = (0, 0);
+    for (key @ (rel, _), (d, s, character, old_text)) in &old {
+        let location = format!("{}: dialogue {d}, speech {s} ({character})", rel.display());
+        match added.remove(key) {
+            Some((.., new_text)) if new_text == old_text => {}
+            Some((.., new_text)) => {
+                println!("{location}\n  - {old_text:?}\n  + {new_text:?}");
+                changed += 1;
+            }
+            None => {
+                println!("{location}: removed\n  - {old_text:?}");
+                removed += 1;
+            }
+        }
+    }
+    for (key @ (rel, _), (d, s, character, new_text)) in &new {
+        if added.contains_key(key) {
+            println!(
+                "{}: dialogue {d}, speech {s} ({character}): added\n  + {new_text:?}",
+                rel.display()
+            );
+        }
+    }
+    println!(
+        "{changed} changed, {} added, {removed} removed",
+        added.len()
+    );
+    Ok(())
+}
+
+/// A text segment in a translation file: the file, the speech's id (or its
+/// dialogue and speech index if it has none) and the segment's index.
+type SegmentKey = (PathBuf, String, usize);
+
+/// The translated segments of `dd`, each keyed by where it is.
+fn translated_segments(dd: &DialogueData) -> HashMap<SegmentKey, String> {
+    let mut segments = HashMap::new();
+    for fd in &dd.files {
+        let rel = &fd.path;
+        for (d, dialogue) in fd.dialogues.iter().enumerate() {
+            for (s, speech) in dialogue.iter().enumerate() {
+                let id = speech.id.clone().unwrap_or_else(|| format!("{d}:{s}"));
+                for (k, (text, range)) in speech.text.iter().enumerate() {
+                    if !text.is_empty() && fd.original_text(range, dd.encoding) != text.as_str() {
+                        segments.insert((rel.to_owned(), id.clone(), k), text.clone());
+                    }
+                }
+            }
+        }
+    }
+    segments
+}
+
+/// Fills in the untranslated segments of `base` with the translations from
+/// each of `contributions`, taking the first one's where several translated
+/// the same segment, and listing those that disagree.
+fn merge_files(base: PathBuf, contributions: &[PathBuf], options: &ImportOptions) -> Result<()> {
+    let mut dd = DialogueData::read(&base)?;
+    let contributed = contributions
+        .iter()
+        .map(|path| Ok((path, translated_segments(&DialogueData::read(path)?))))
+        .collect::<Result<Vec<_>>>()?;
+    let (mut imported, mut conflicts) = (0, 0);
+    for fd in dd.files.iter_mut() {
+        let rel = fd.path.to_owned();
+        let mut dialogues = std::mem::take(&mut fd.dialogues);
+        for (d, dialogue) in dialogues.iter_mut().enumerate() {
+            for (s, speech) in dialogue.iter_mut().enumerate() {
+                let id = speech.id.clone().unwrap_or_else(|| format!("{d}:{s}"));
+                for (k, (text, range)) in speech.text.iter_mut().enumerate() {
+                    let key = (rel.clone(), id.clone(), k);
+                    let original = fd.original_text(range, dd.encoding);
+                    // the base's own translations count as the first
+                    let mut chosen = (!text.is_empty() && original != text.as_str())
+                        .then(|| (&base, text.clone()));
+                    for (path, segments) in &contributed {
+                        let Some(translation) = segments.get(&key) else {
+                            continue;
+                        };
+                        match &chosen {
+                            None => chosen = Some((path, translation.clone())),
+                            Some((first, kept)) if kept != translation => {
+                                println!(
+                                    "{}: dialogue {d}, speech {s}, segment {k}: {path:?} has {translation:?}, but {first:?} has {kept:?}, which is kept",
+                                    rel.display()
+                                );
+                                conflicts += 1;
+                            }
+                            Some(_) => {}
+                        }
+                    }
+                    if let Some((from, translation)) = chosen
+                        && from != &base
+                    {
+                        *text = translation;
+                        imported += 1;
+                    }
+                }
+            }
+        }
+        fd.dialogues = dialogues;
     }
+    if conflicts > 0 {
+        println!("{conflicts} conflicting translations");
+    }
+    save_import(dd, &base, imported, options)
+}
 
-    let dialogue = DialogueData {
-        game_data_root: data_dir,
-        files,
+/// Gives every speech of `dd` that isn't translated at all the translation
+/// it has in `base` instead, if any, returning how many did. Partly
+/// translated speeches are left as they are.
+fn fall_back_to(dd: &mut DialogueData, base: &HashMap<SegmentKey, String>) -> usize {
+    let mut filled = 0;
+    for fd in dd.files.iter_mut() {
+        let rel = fd.path.to_owned();
+        let mut dialogues = std::mem::take(&mut fd.dialogues);
+        for (d, dialogue) in dialogues.iter_mut().enumerate() {
+            for (s, speech) in dialogue.iter_mut().enumerate() {
+                let untranslated = speech
+                    .text
+                    .iter()
+                    .all(|(text, range)| fd.original_text(range, dd.encoding) == text.as_str());
+                if !untranslated {
+                    continue;
+                }
+                let id = speech.id.clone().unwrap_or_else(|| format!("{d}:{s}"));
+                let mut found = false;
+                for (k, (text, _)) in speech.text.iter_mut().enumerate() {
+                    if let Some(translation) = base.get(&(rel.clone(), id.clone(), k)) {
+                        text.clone_from(translation);
+                        found = true;
+                    }
+                }
+                filled += usize::from(found);
+            }
+        }
+        fd.dialogues = dialogues;
+    }
+    filled
+}
+
+/// Describes how the control codes of a translated segment differ from the
+/// original's, if they do.
+fn code_mismatch(original: &str, translation: &str) -> Option<String> {
+    let (old, new) = (control_codes(original), control_codes(translation));
+    if old == new {
+        return None;
+    }
+    let count = |codes: &[&str]| {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for code in codes {
+            *counts.entry(code.to_string()).or_default() += 1;
+        }
+        counts
     };
+    let (old_counts, new_counts) = (count(&old), count(&new));
+    if old_counts == new_counts {
+        return Some(format!(
+            "control codes reordered: {} became {}",
+            old.join(" "),
+            new.join(" ")
+        ));
+    }
+    let difference = |a: &BTreeMap<String, usize>, b: &BTreeMap<String, usize>| {
+        let mut codes = vec![];
+        for (code, n) in a {
+            let extra = n.saturating_sub(b.get(code).copied().unwrap_or(0));
+            codes.extend(std::iter::repeat_n(code.as_str(), extra));
+        }
+        codes.join(" ")
+    };
+    let (missing, added) = (
+        difference(&old_counts, &new_counts),
+        difference(&new_counts, &old_counts),
+    );
+    Some(match (missing.is_empty(), added.is_empty()) {
+        (false, false) => format!("control codes changed: missing {missing}, added {added}"),
+        (false, true) => format!("control codes missing: {missing}"),
+        _ => format!("control codes added: {added}"),
+    })
+}
 
-    let j = serde_json::to_string(&dialogue)?;
-    let mut outfile = std::fs::File::create(&output)?;
-    outfile.write_all(j.as_bytes())?;
+/// Lines a message box shows at once.
+const BOX_LINES: usize = 3;
+
+/// The most lines the translation of `speech` puts in one message box
+/// before a `<NOD` or `<CLR`, if that's more than the box can show. The
+/// codes between its segments come from the original script, since they
+/// aren't translated.
+fn box_overflow(fd: &FileData, speech: &Speech, encoding: TextEncoding) -> Option<usize> {
+    let mut shown = String::new();
+    let mut last_end = None;
+    for (text, range) in &speech.text {
+        if let Some(end) = last_end {
+            shown.push_str(&fd.original_text(&(end..range.start), encoding));
+        }
+        shown.push_str(text);
+        last_end = Some(range.end);
+    }
+    let most = shown
+        .replace("<CLR", "<NOD")
+        .split("<NOD")
+        // the line break that usually follows a <NOD doesn't start another
+        // line in the box
+        .map(|text| text.trim_matches('\n').lines().count())
+        .max()?;
+    (most > BOX_LINES).then_some(most)
+}
+
+/// Reports every line of translated dialogue that is wider than `max_width`
+/// characters and would be clipped by the message box, every translated
+/// speech with more lines than fit in the box between two <NOD or <CLR, and
+/// every translated segment whose control codes differ from the original's.
+/// Lines end at a line break or a <NOD, and control codes don't count
+/// towards their width.
+fn lint(translation_file: PathBuf, max_width: usize) -> Result<()> {
+    let dd = DialogueData::read(&translation_file)?;
+    let mut problems = 0;
+    for fd in &dd.files {
+        let rel = &fd.path;
+        for (d, dialogue) in fd.dialogues.iter().enumerate() {
+            for (s, speech) in dialogue.iter().enumerate() {
+                for (k, (text, range)) in speech.text.iter().enumerate() {
+                    let original = fd.original_text(range, dd.encoding);
+                    if let Some(mismatch) = code_mismatch(&original, text) {
+                        println!(
+                            "{}: dialogue {d}, speech {s}, segment {k}: {mismatch}",
+                            rel.display()
+                        );
+                        problems += 1;
+                    }
+                    if original != text.as_str()
+                        && let Some(command) = fd.argument_of(range)
+                    {
+                        println!(
+                            "{}: dialogue {d}, speech {s}, segment {k}: translates an argument of <{command}, which the game doesn't show",
+                            rel.display()
+                        );
+                        problems += 1;
+                    }
+                }
+                let translated = speech
+                    .text
+                    .iter()
+                    .any(|(text, range)| fd.original_text(range, dd.encoding) != text.as_str());
+                if !translated {
+                    continue;
+                }
+                if let Some(lines) = box_overflow(fd, speech, dd.encoding) {
+                    println!(
+                        "{}: dialogue {d}, speech {s}: {lines} lines in one message box (max {BOX_LINES}) without a <NOD or <CLR",
+                        rel.display()
+                    );
+                    problems += 1;
+                }
+                let lines = speech
+                    .text
+                    .iter()
+                    .flat_map(|(text, _)| text.split('\n'))
+                    .flat_map(|line| line.split("<NOD"));
+                for line in lines {
+                    let width = visible_len(line);
+                    if width > max_width {
+                        println!(
+                            "{}: dialogue {d}, speech {s}: line is {width} characters wide (max {max_width}): {line:?}",
+                            rel.display()
+                        );
+                        problems += 1;
+                    }
+                }
+            }
+        }
+    }
+    match problems {
+        0 => Ok(()),
+        n => Err(Failure::Validation(format!("found {n} problems in {translation_file:?}")).into()),
+    }
+}
+
+/// Counts the speeches that haven't been translated yet. With
+/// `untranslated_gate`, fails if there are more than `threshold` of them.
+fn stats(translation_file: PathBuf, untranslated_gate: bool, threshold: usize) -> Result<()> {
+    let dd = DialogueData::read(&translation_file)?;
+    let mut total = 0;
+    let mut untranslated = vec![];
+    for fd in &dd.files {
+        let rel = &fd.path;
+        for (d, dialogue) in fd.dialogues.iter().enumerate() {
+            for (s, speech) in dialogue.iter().enumerate() {
+                total += 1;
+                if speech.text.iter().any(|(text, range)| {
+                    text.is_empty() || fd.original_text(range, dd.encoding) == text.as_str()
+                }) {
+                    untranslated.push(format!("{}: dialogue {d}, speech {s}", rel.display()));
+                }
+            }
+        }
+    }
+    println!(
+        "{} of {total} speeches translated, {} untranslated",
+        total - untranslated.len(),
+        untranslated.len()
+    );
+    for location in untranslated.iter().take(5) {
+        println!("  {location}");
+    }
+    if untranslated.len() > 5 {
+        println!("  ...");
+    }
+    match untranslated_gate && untranslated.len() > threshold {
+        true => Err(Failure::Validation(format!(
+            "{} untranslated speeches in {translation_file:?} (at most {threshold} allowed)",
+            untranslated.len()
+        ))
+        .into()),
+        false => Ok(()),
+    }
+}
+
+/// How far along the translation is, as `--checkpoint` records it.
+#[derive(Serialize)]
+struct Checkpoint {
+    done: usize,
+    total: usize,
+    files: BTreeMap<String, FileProgress>,
+}
+
+#[derive(Serialize)]
+struct FileProgress {
+    done: usize,
+    total: usize,
+    /// The ids of the speeches marked done, or their `dialogue:speech`
+    /// indices for speeches without one.
+    done_speeches: Vec<String>,
+}
+
+impl Checkpoint {
+    fn of(dd: &DialogueData) -> Checkpoint {
+        let mut progress = Checkpoint {
+            done: 0,
+            total: 0,
+            files: BTreeMap::new(),
+        };
+        for fd in &dd.files {
+            let mut file = FileProgress {
+                done: 0,
+                total: 0,
+                done_speeches: vec![],
+            };
+            for (d, dialogue) in fd.dialogues.iter().enumerate() {
+                for (s, speech) in dialogue.iter().enumerate() {
+                    file.total += 1;
+                    if speech.done {
+                        file.done += 1;
+                        file.done_speeches
+                            .push(speech.id.clone().unwrap_or_else(|| format!("{d}:{s}")));
+                    }
+                }
+            }
+            progress.done += file.done;
+            progress.total += file.total;
+            progress.files.insert(fd.path.display().to_string(), file);
+        }
+        progress
+    }
+
+    fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Shows how many speeches of each file have been marked done, and of the
+/// whole translation. With `checkpoint`, also writes that down as JSON for
+/// dashboards to pick up.
+fn status(translation_file: PathBuf, checkpoint: Option<PathBuf>) -> Result<()> {
+    let dd = DialogueData::read(&translation_file)?;
+    let percent = |done: usize, total: usize| match total {
+        0 => 100.0,
+        total => done as f64 * 100.0 / total as f64,
+    };
+    let progress = Checkpoint::of(&dd);
+    for (path, file) in &progress.files {
+        println!(
+            "{path}: {} of {} speeches done ({:.0}%)",
+            file.done,
+            file.total,
+            percent(file.done, file.total)
+        );
+    }
+    println!(
+        "In all: {} of {} speeches done ({:.1}%)",
+        progress.done,
+        progress.total,
+        percent(progress.done, progress.total)
+    );
+    if let Some(path) = checkpoint {
+        progress.write(&path)?;
+    }
+    Ok(())
+}
+
+/// A speech's text as shown by “translate”, with its segments' line breaks
+/// indented under the speaker.
+fn show_speech(fd: &FileData, speech: &Speech, encoding: TextEncoding, original: bool) -> String {
+    let text: String = speech
+        .text
+        .iter()
+        .map(|(text, range)| match original {
+            true => fd.original_text(range, encoding),
+            false => Cow::Borrowed(text.as_str()),
+        })
+        .collect();
+    format!("{}: {}", speech.speaker(), text.replace('\n', "\n    "))
+}
+
+/// The file, dialogue and speech index of every speech of `dd` that isn't
+/// translated at all.
+fn untranslated_speeches(dd: &DialogueData) -> Vec<(usize, usize, usize)> {
+    let mut queue = vec![];
+    for (f, fd) in dd.files.iter().enumerate() {
+        for (d, dialogue) in fd.dialogues.iter().enumerate() {
+            for (s, speech) in dialogue.iter().enumerate() {
+                let untranslated = speech
+                    .text
+                    .iter()
+                    .all(|(text, range)| fd.original_text(range, dd.encoding) == text.as_str());
+                if untranslated {
+                    queue.push((f, d, s));
+                }
+            }
+        }
+    }
+    queue
+}
+
+/// Where “translate” saves what's typed in.
+struct TranslateOutput {
+    translation_file: PathBuf,
+    pretty: bool,
+    checkpoint: Option<PathBuf>,
+}
+
+impl TranslateOutput {
+    /// Marks the speech `(f, d, s)` of `dd` done, as it's just been
+    /// translated, and saves `dd` and the checkpoint.
+    fn save(&self, dd: &mut DialogueData, (f, d, s): (usize, usize, usize)) -> Result<()> {
+        dd.files[f].dialogues[d][s].done = true;
+        dd.save(&self.translation_file, self.pretty)?;
+        if let Some(checkpoint) = &self.checkpoint {
+            Checkpoint::of(dd).write(checkpoint)?;
+        }
+        Ok(())
+    }
+}
+
+/// Steps through the untranslated speeches, marking each done as it's
+/// translated and saving the translation file (and `checkpoint`, if any) after
+/// it: on a screen of its own in a terminal (see [`Translator`]), or one line
+/// of input per segment otherwise, e.g. when piped.
+fn translate(translation_file: PathBuf, pretty: bool, checkpoint: Option<PathBuf>) -> Result<()> {
+    let pretty = pretty || DialogueData::is_pretty(&translation_file);
+    let dd = DialogueData::read(&translation_file)?;
+    // the speeches that were untranslated to begin with, so that going back
+    // finds the ones just translated
+    let queue = untranslated_speeches(&dd);
+    if queue.is_empty() {
+        println!("{translation_file:?} has no untranslated speeches");
+        return Ok(());
+    }
+    let output = TranslateOutput {
+        translation_file: translation_file.clone(),
+        pretty,
+        checkpoint,
+    };
+    let translated = match std::io::stdin().is_terminal() && std::io::stdout().is_terminal() {
+        true => Translator::new(dd, queue, output).run()?,
+        false => translate_lines(dd, queue, &output)?,
+    };
+    println!("Translated {translated} speeches into {translation_file:?}");
     Ok(())
 }
 
-fn write(translation_file: PathBuf, output_dir: PathBuf) -> Result<()> {
-    let file = std::fs::File::open(translation_file)?;
-    let reader = BufReader::new(file);
-    let dd: DialogueData = serde_json::from_reader(reader)?;
-    let dir = output_dir;
-    std::fs::create_dir_all(&dir)?;
-    for fd in dd.files {
-        let p = dir.join(fd.path.strip_prefix(&dd.game_data_root)?);
-        let s = fd.reconstruct();
-        let enc = tsc_encode(s);
-        std::fs::create_dir_all(
-            p.parent()
-                .ok_or(anyhow!("couldn't create parent directory"))?,
-        )?;
-        let mut outfile = std::fs::File::create(&p)?;
-        outfile.write_all(&enc)?;
-        println!("Wrote {p:?}");
+/// The line-based “translate”, returning how many speeches were translated.
+fn translate_lines(
+    mut dd: DialogueData,
+    queue: Vec<(usize, usize, usize)>,
+    output: &TranslateOutput,
+) -> Result<usize> {
+    let encoding = dd.encoding;
+    println!(
+        "{} untranslated speeches. Type each translation, with \\n for a line break, \
+         or an empty line to skip it; “:p” goes back, “:f PATTERN” jumps to the first \
+         script matching PATTERN (e.g. Stage/Pens*), and “:q” quits.",
+        queue.len()
+    );
+    let mut lines = std::io::stdin().lines();
+    let mut translated = 0;
+    let mut i = 0;
+    'speeches: while i < queue.len() {
+        let (f, d, s) = queue[i];
+        let fd = &dd.files[f];
+        let speech = &fd.dialogues[d][s];
+        let event = speech
+            .event
+            .map(|e| format!(", #{e:04}"))
+            .unwrap_or_default();
+        println!("\n[{}/{}] {}{event}", i + 1, queue.len(), fd.path.display());
+        if let Some(before) = s.checked_sub(1).map(|s| &fd.dialogues[d][s]) {
+            println!("  before: {}", show_speech(fd, before, encoding, false));
+        }
+        println!("  {}", show_speech(fd, speech, encoding, true));
+        if let Some(after) = fd.dialogues[d].get(s + 1) {
+            println!("  after: {}", show_speech(fd, after, encoding, false));
+        }
+        if let Some(context) = &speech.context {
+            println!("  context: {context}");
+        }
+        let mut texts = vec![];
+        for (k, (text, range)) in speech.text.iter().enumerate() {
+            if speech.text.len() > 1 {
+                println!("  segment {} of {}:", k + 1, speech.text.len());
+            }
+            let original = fd.original_text(range, encoding);
+            if *text != original {
+                println!("  (translated as {text:?})");
+            }
+            print!("> ");
+            std::io::stdout().flush()?;
+            let Some(line) = lines.next().transpose()? else {
+                break 'speeches;
+            };
+            let line = line.trim_end_matches('\r');
+            match line
+                .split_once(' ')
+                .map_or((line, ""), |(c, rest)| (c, rest.trim()))
+            {
+                (":q", _) => break 'speeches,
+                (":p", _) => {
+                    i = i.saturating_sub(1);
+                    continue 'speeches;
+                }
+                (":f", pattern) => {
+                    let pattern = match Pattern::new(pattern) {
+                        Ok(pattern) => pattern,
+                        Err(e) => {
+                            println!("{e}");
+                            continue 'speeches;
+                        }
+                    };
+                    match queue
+                        .iter()
+                        .position(|&(f, ..)| pattern.matches_path(&dd.files[f].path))
+                    {
+                        Some(found) => i = found,
+                        None => println!("No untranslated speech in a script matching {pattern}"),
+                    }
+                    continue 'speeches;
+                }
+                ("", _) => texts.push(None),
+                _ => texts.push(Some(line.replace("\\n", "\n"))),
+            }
+        }
+        if texts.iter().any(Option::is_some) {
+            let speech = &mut dd.files[f].dialogues[d][s];
+            for ((text, _), new) in speech.text.iter_mut().zip(texts) {
+                if let Some(new) = new {
+                    *text = new;
+                }
+            }
+            output.save(&mut dd, (f, d, s))?;
+            translated += 1;
+        }
+        i += 1;
+    }
+    Ok(translated)
+}
+
+/// What the keys typed into the “translate” screen go to.
+#[derive(Debug, PartialEq)]
+enum Prompt {
+    /// The translation of the current segment.
+    Segment,
+    /// The pattern of a script to jump to, after Ctrl-F.
+    File,
+}
+
+/// The keys of the “translate” screen, shown at the bottom of it.
+const TRANSLATE_KEYS: [&str; 3] = [
+    "Enter: next segment, saving the speech after the last · Ctrl-O: line break",
+    "Tab/Shift-Tab: switch segments · PgDn/PgUp: next/previous speech",
+    "Ctrl-F: jump to a script · Esc: quit",
+];
+
+/// `line` cut to `width` columns, with an ellipsis if anything was cut.
+fn fit(line: &str, width: usize) -> String {
+    if line.width() <= width {
+        return line.to_string();
+    }
+    let mut fitted = String::new();
+    let mut used = 0;
+    for c in line.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w + 1 > width {
+            break;
+        }
+        fitted.push(c);
+        used += w;
+    }
+    fitted.push('…');
+    fitted
+}
+
+/// The full-screen “translate”: shows an untranslated speech at a time with
+/// the speeches around it and its context, and edits the translation of each
+/// of its segments. Typing into a segment that was left untranslated starts
+/// from scratch, and a segment left empty stays as it is.
+struct Translator {
+    dd: DialogueData,
+    output: TranslateOutput,
+    /// The speeches to go through, by file, dialogue and speech index.
+    queue: Vec<(usize, usize, usize)>,
+    /// Whether each speech of the queue has been translated since.
+    done: Vec<bool>,
+    /// The speech of the queue being translated, and the segment within it.
+    i: usize,
+    segment: usize,
+    /// What's been typed for each segment of the speech.
+    drafts: Vec<String>,
+    prompt: Prompt,
+    pattern: String,
+    /// Where the cursor is in what's being typed, in characters.
+    cursor: usize,
+    /// What happened last, shown under the speech.
+    message: String,
+}
+
+impl Translator {
+    fn new(
+        dd: DialogueData,
+        queue: Vec<(usize, usize, usize)>,
+        output: TranslateOutput,
+    ) -> Translator {
+        let mut translator = Translator {
+            dd,
+            output,
+            done: vec![false; queue.len()],
+            queue,
+            i: 0,
+            segment: 0,
+            drafts: vec![],
+            prompt: Prompt::Segment,
+            pattern: String::new(),
+            cursor: 0,
+            message: String::new(),
+        };
+        translator.load();
+        translator
+    }
+
+    /// Draws the screen and handles keys until told to quit, returning how
+    /// many speeches were translated.
+    fn run(mut self) -> Result<usize> {
+        let mut stdout = std::io::stdout();
+        terminal::enable_raw_mode()?;
+        // the alternate screen leaves the shell's scrollback alone
+        execute!(stdout, EnterAlternateScreen)?;
+        let result = (|| -> Result<()> {
+            loop {
+                let (columns, rows) = terminal::size()?;
+                let (lines, (x, y)) = self.screen(columns as usize);
+                queue!(stdout, Hide)?;
+                for (row, line) in (0..rows).zip(lines) {
+                    queue!(
+                        stdout,
+                        MoveTo(0, row),
+                        Clear(ClearType::CurrentLine),
+                        PrintStyledContent(line)
+                    )?;
+                }
+                queue!(stdout, Clear(ClearType::FromCursorDown))?;
+                let y = (y as u16).min(rows.saturating_sub(1));
+                queue!(stdout, MoveTo(x as u16, y), Show)?;
+                stdout.flush()?;
+                // a resize only needs the screen drawn again
+                if let event::Event::Key(key) = event::read()?
+                    && key.kind == KeyEventKind::Press
+                    && !self.handle(key)?
+                {
+                    return Ok(());
+                }
+            }
+        })();
+        execute!(stdout, LeaveAlternateScreen, Show)?;
+        terminal::disable_raw_mode()?;
+        result?;
+        Ok(self.done.iter().filter(|&&done| done).count())
+    }
+
+    fn current(&self) -> (&FileData, &Speech) {
+        let (f, d, s) = self.queue[self.i];
+        let fd = &self.dd.files[f];
+        (fd, &fd.dialogues[d][s])
+    }
+
+    /// Starts on the speech `i` of the queue, with the translations it has.
+    fn load(&mut self) {
+        let (fd, speech) = self.current();
+        self.drafts = speech
+            .text
+            .iter()
+            .map(
+                |(text, range)| match fd.original_text(range, self.dd.encoding) == *text {
+                    true => String::new(),
+                    false => text.clone(),
+                },
+            )
+            .collect();
+        self.segment = 0;
+        self.cursor = self.buffer().chars().count();
+    }
+
+    fn buffer(&mut self) -> &mut String {
+        match self.prompt {
+            Prompt::Segment => &mut self.drafts[self.segment],
+            Prompt::File => &mut self.pattern,
+        }
+    }
+
+    /// Goes to the speech `i` of the queue, dropping what's been typed into
+    /// the current one.
+    fn go_to(&mut self, i: usize) {
+        self.i = i;
+        self.load();
+    }
+
+    fn switch_segment(&mut self, segment: usize) {
+        self.segment = segment;
+        self.cursor = self.buffer().chars().count();
+    }
+
+    /// Saves the translations typed into the speech, if any, and moves on.
+    fn save_speech(&mut self) -> Result<()> {
+        let (f, d, s) = self.queue[self.i];
+        let speech = &mut self.dd.files[f].dialogues[d][s];
+        let mut changed = false;
+        for ((text, _), draft) in speech.text.iter_mut().zip(&self.drafts) {
+            if !draft.is_empty() && draft != text {
+                *text = draft.clone();
+                changed = true;
+            }
+        }
+        if changed {
+            self.output.save(&mut self.dd, (f, d, s))?;
+            self.done[self.i] = true;
+            self.message = format!("Saved to {:?}", self.output.translation_file);
+        }
+        match self.i + 1 < self.queue.len() {
+            true => self.go_to(self.i + 1),
+            false => {
+                self.message += match changed {
+                    true => "; that was the last speech",
+                    false => "That was the last speech",
+                };
+                self.load();
+            }
+        }
+        Ok(())
+    }
+
+    /// Jumps to the first speech of the queue in a script matching the
+    /// pattern typed.
+    fn jump(&mut self) {
+        self.prompt = Prompt::Segment;
+        let pattern = match Pattern::new(self.pattern.trim()) {
+            Ok(pattern) => pattern,
+            Err(e) => {
+                self.message = format!("Invalid pattern: {e}");
+                self.switch_segment(self.segment);
+                return;
+            }
+        };
+        let files = &self.dd.files;
+        match self
+            .queue
+            .iter()
+            .position(|&(f, ..)| pattern.matches_path(&files[f].path))
+        {
+            Some(i) => {
+                self.message.clear();
+                self.go_to(i);
+            }
+            None => {
+                self.message = format!("No untranslated speech in a script matching {pattern}");
+                self.switch_segment(self.segment);
+            }
+        }
+    }
+
+    /// Handles a key, returning whether to carry on.
+    fn handle(&mut self, key: KeyEvent) -> Result<bool> {
+        let cursor = self.cursor;
+        let len = self.buffer().chars().count();
+        let byte = |buffer: &str, chars: usize| {
+            buffer
+                .char_indices()
+                .nth(chars)
+                .map_or(buffer.len(), |(i, _)| i)
+        };
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        // Ctrl-N and Ctrl-P for terminals without the page keys
+        let code = match key.code {
+            KeyCode::Char('n') if ctrl => KeyCode::PageDown,
+            KeyCode::Char('p') if ctrl => KeyCode::PageUp,
+            code => code,
+        };
+        match (&self.prompt, code) {
+            (_, KeyCode::Char('c')) | (Prompt::Segment, KeyCode::Char('q')) if ctrl => {
+                return Ok(false);
+            }
+            (Prompt::Segment, KeyCode::Esc) => return Ok(false),
+            (Prompt::File, KeyCode::Esc) => {
+                self.prompt = Prompt::Segment;
+                self.switch_segment(self.segment);
+            }
+            (Prompt::File, KeyCode::Enter) => self.jump(),
+            (Prompt::Segment, KeyCode::Enter) => match self.segment + 1 < self.drafts.len() {
+                true => self.switch_segment(self.segment + 1),
+                false => self.save_speech()?,
+            },
+            (Prompt::Segment, KeyCode::Tab) if self.segment + 1 < self.drafts.len() => {
+                self.switch_segment(self.segment + 1);
+            }
+            (Prompt::Segment, KeyCode::BackTab) if self.segment > 0 => {
+                self.switch_segment(self.segment - 1);
+            }
+            (Prompt::Segment, KeyCode::PageDown) => match self.i + 1 < self.queue.len() {
+                true => self.go_to(self.i + 1),
+                false => self.message = "That was the last speech".to_string(),
+            },
+            (Prompt::Segment, KeyCode::PageUp) => self.go_to(self.i.saturating_sub(1)),
+            (Prompt::Segment, KeyCode::Char('f')) if ctrl => {
+                self.prompt = Prompt::File;
+                self.pattern.clear();
+                self.cursor = 0;
+            }
+            (Prompt::Segment, KeyCode::Char('o')) if ctrl => {
+                let buffer = self.buffer();
+                let at = byte(buffer, cursor);
+                buffer.insert(at, '\n');
+                self.cursor += 1;
+            }
+            (_, KeyCode::Char(c)) if !ctrl => {
+                let buffer = self.buffer();
+                let at = byte(buffer, cursor);
+                buffer.insert(at, c);
+                self.cursor += 1;
+            }
+            (_, KeyCode::Backspace) if cursor > 0 => {
+                let buffer = self.buffer();
+                let at = byte(buffer, cursor - 1);
+                buffer.remove(at);
+                self.cursor -= 1;
+            }
+            (_, KeyCode::Delete) if cursor < len => {
+                let buffer = self.buffer();
+                let at = byte(buffer, cursor);
+                buffer.remove(at);
+            }
+            (_, KeyCode::Left) => self.cursor = cursor.saturating_sub(1),
+            (_, KeyCode::Right) => self.cursor = (cursor + 1).min(len),
+            (_, KeyCode::Home) => self.cursor = 0,
+            (_, KeyCode::End) => self.cursor = len,
+            _ => {}
+        }
+        Ok(true)
     }
+
+    /// The lines of the screen, cut to `width` columns, and where the cursor
+    /// goes on it.
+    fn screen(&self, width: usize) -> (Vec<StyledContent<String>>, (usize, usize)) {
+        let (fd, speech) = self.current();
+        let encoding = self.dd.encoding;
+        let (f, d, s) = self.queue[self.i];
+        // each line with how it's highlighted
+        let mut lines: Vec<(String, Option<Attribute>)> = vec![];
+        let done = self.done.iter().filter(|&&done| done).count();
+        lines.push((
+            format!(
+                " {}: speech {} of {} untranslated, {done} translated so far ",
+                self.output.translation_file.display(),
+                self.i + 1,
+                self.queue.len(),
+            ),
+            Some(Attribute::Reverse),
+        ));
+        let event = speech
+            .event
+            .map(|e| format!(", #{e:04}"))
+            .unwrap_or_default();
+        lines.push((
+            format!(" {}{event}, dialogue {d}, speech {s}", fd.path.display()),
+            None,
+        ));
+        lines.push((String::new(), None));
+        let dialogue = &self.dd.files[f].dialogues[d];
+        let mut show = |label: &str, text: String, attribute: Attribute| {
+            for (n, line) in text.lines().enumerate() {
+                let label = if n == 0 { label } else { "" };
+                lines.push((format!(" {label:>8}  {line}"), Some(attribute)));
+            }
+        };
+        if let Some(before) = s.checked_sub(1).map(|s| &dialogue[s]) {
+            show(
+                "before",
+                show_speech(fd, before, encoding, false),
+                Attribute::Dim,
+            );
+        }
+        show(
+            "original",
+            show_speech(fd, speech, encoding, true),
+            Attribute::Bold,
+        );
+        if let Some(after) = dialogue.get(s + 1) {
+            show(
+                "after",
+                show_speech(fd, after, encoding, false),
+                Attribute::Dim,
+            );
+        }
+        if let Some(context) = &speech.context {
+            show("context", context.clone(), Attribute::Dim);
+        }
+        if let Some(note) = &speech.translator_note {
+            show("note", note.clone(), Attribute::Dim);
+        }
+        lines.push((String::new(), None));
+        let mut cursor = (0, 0);
+        for (k, ((_, range), draft)) in speech.text.iter().zip(&self.drafts).enumerate() {
+            let current = self.prompt == Prompt::Segment && k == self.segment;
+            let heading = format!(
+                " Segment {} of {}: {:?}",
+                k + 1,
+                speech.text.len(),
+                fd.original_text(range, encoding)
+            );
+            lines.push((heading, current.then_some(Attribute::Bold)));
+            if current {
+                let before: String = draft.chars().take(self.cursor).collect();
+                let column = before.rsplit('\n').next().unwrap_or_default();
+                cursor = (
+                    3 + column.width(),
+                    lines.len() + before.matches('\n').count(),
+                );
+            }
+            for (n, line) in draft.split('\n').enumerate() {
+                let prompt = if n == 0 { ">" } else { " " };
+                lines.push((format!(" {prompt} {line}"), None));
+            }
+        }
+        lines.push((String::new(), None));
+        match self.prompt {
+            Prompt::Segment => lines.push((format!(" {}", self.message), None)),
+            Prompt::File => {
+                let label = " Jump to the first script matching (e.g. Stage/Pens*): ";
+                let before: String = self.pattern.chars().take(self.cursor).collect();
+                cursor = (label.width() + before.width(), lines.len());
+                lines.push((format!("{label}{}", self.pattern), None));
+            }
+        }
+        for keys in TRANSLATE_KEYS {
+            lines.push((format!(" {keys}"), Some(Attribute::Dim)));
+        }
+        let lines = lines
+            .into_iter()
+            .map(|(line, attribute)| {
+                let line = fit(&line, width).stylize();
+                match attribute {
+                    Some(attribute) => line.attribute(attribute),
+                    None => line,
+                }
+            })
+            .collect();
+        (lines, cursor)
+    }
+}
+
+/// Prints the JSON Schema of the translation file, for editors to validate
+/// and autocomplete it with.
+fn schema() -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(&translation_schema())?);
     Ok(())
 }
 
+/// Reports every translated line of dialogue using glyphs outside the ones
+/// listed in `charset_file`.
+fn validate_glyphs(translation_file: PathBuf, charset_file: PathBuf) -> Result<()> {
+    let dd = DialogueData::read(&translation_file)?;
+    let charset = read_charset(&charset_file)?;
+    match unrenderable(&dd, Some(&charset)) {
+        0 => Ok(()),
+        n => Err(Failure::Validation(format!(
+            "found {n} unsupported glyphs in {translation_file:?}"
+        ))
+        .into()),
+    }
+}
+
+/// Reports every character of the translated text that the scripts' encoding
+/// can't represent, or that isn't in `charset` if given, returning how many
+/// there are.
+fn unrenderable(dd: &DialogueData, charset: Option<&HashSet<char>>) -> usize {
+    let mut problems = 0;
+    for fd in &dd.files {
+        let rel = &fd.path;
+        for (d, dialogue) in fd.dialogues.iter().enumerate() {
+            for (s, speech) in dialogue.iter().enumerate() {
+                for (text, range) in &speech.text {
+                    if fd.original_text(range, dd.encoding) == text.as_str() {
+                        continue;
+                    }
+                    for (i, c) in glyphs(text, fd.escaped) {
+                        let problem = match charset {
+                            _ if !dd.encoding.can_encode(c) => "isn't in the scripts' encoding",
+                            Some(charset) if !charset.contains(&c) => "isn't in the charset",
+                            _ => continue,
+                        };
+                        println!(
+                            "{}: dialogue {d}, speech {s}: {c:?} (U+{:04X}) at position {i} {problem}: {text:?}",
+                            rel.display(),
+                            c as u32
+                        );
+                        problems += 1;
+                    }
+                }
+            }
+        }
+    }
+    problems
+}
+
+/// The characters of `text`, each with its position, leaving out the
+/// `\x{NN}` escapes of an `escaped` file, which stand for bytes rather than
+/// characters.
+fn glyphs(text: &str, escaped: bool) -> Vec<(usize, char)> {
+    let is_escape = |rest: &str| {
+        let b = rest.as_bytes();
+        b.len() >= 6
+            && b.starts_with(b"\\x{")
+            && b[3..5].iter().all(u8::is_ascii_hexdigit)
+            && b[5] == b'}'
+    };
+    let mut glyphs = vec![];
+    let mut skip_to = 0;
+    for (i, (at, c)) in text.char_indices().enumerate() {
+        if at < skip_to {
+            continue;
+        }
+        if escaped && is_escape(&text[at..]) {
+            skip_to = at + 6;
+            continue;
+        }
+        glyphs.push((i, c));
+    }
+    glyphs
+}
+
+/// Checks that the scripts in `game_data` (or the folder the translation was
+/// dumped from) are the ones the translation was dumped from, by their hashes.
+fn verify(translation_file: PathBuf, game_data: Option<PathBuf>) -> Result<()> {
+    let dd = DialogueData::read(&translation_file)?;
+    let root = game_data.unwrap_or_else(|| dd.game_data_root.clone());
+    let everything = DumpOptions {
+        pattern: Some("**/*".to_string()),
+        ..Default::default()
+    };
+    let archive: Option<HashMap<_, _>> = match is_zip(&root) {
+        true => Some(zip_scripts(&root, &everything)?.into_iter().collect()),
+        false => None,
+    };
+    let (mut checked, mut problems) = (0, 0);
+    for fd in &dd.files {
+        let Some(expected) = &fd.source_sha256 else {
+            continue;
+        };
+        let path = root.join(&fd.path);
+        checked += 1;
+        let contents = match &archive {
+            None => std::fs::read(&path),
+            Some(archive) => archive.get(&path).cloned().ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::NotFound, "not in the archive")
+            }),
+        };
+        match contents {
+            Ok(contents) if sha256_hex(&contents) == *expected => {}
+            Ok(_) => {
+                println!("{path:?} differs from the script the translation was dumped from");
+                problems += 1;
+            }
+            Err(e) => {
+                println!("{path:?} can't be read: {e}");
+                problems += 1;
+            }
+        }
+    }
+    if checked < dd.files.len() {
+        warn!(
+            "{} files in {translation_file:?} have no hash to check, since they were dumped \
+             by an older version",
+            dd.files.len() - checked
+        );
+    }
+    match problems {
+        0 => {
+            println!("{checked} scripts match {translation_file:?}");
+            Ok(())
+        }
+        n => Err(Failure::Validation(format!(
+            "{n} of {checked} scripts don't match {translation_file:?}"
+        ))
+        .into()),
+    }
+}
+
 // from https://github.com/RazrFalcon/pico-args/blob/master/examples/app.rs
 fn parse_path(s: &std::ffi::OsStr) -> Result<std::path::PathBuf, &'static str> {
     Ok(s.into())
 }
 
-fn help() -> Result<()> {
-    Err(anyhow!(
-        "Usage: doukutsu-extractor [OPTIONS] COMMAND
+/// Parses a byte written in decimal, or in hex with a `0x` prefix.
+fn parse_byte(s: &str) -> Result<u8, std::num::ParseIntError> {
+    match s.strip_prefix("0x").or(s.strip_prefix("0X")) {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+}
+
+/// The kinds of failure that get an exit code of their own, so that scripts
+/// can tell them apart. I/O errors get theirs too.
+#[derive(Debug)]
+enum Failure {
+    /// The command line doesn't make sense.
+    Usage(String),
+    /// An input file couldn't be parsed.
+    Parse(String),
+    /// A check like `lint` or `verify` found problems.
+    Validation(String),
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Failure::Usage(message) | Failure::Parse(message) | Failure::Validation(message) => {
+                f.write_str(message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Failure {}
+
+fn usage(message: impl Into<String>) -> anyhow::Error {
+    Failure::Usage(message.into()).into()
+}
+
+/// The exit code for `e`, as listed by [`help`].
+fn exit_code(e: &anyhow::Error) -> u8 {
+    for cause in e.chain() {
+        if let Some(failure) = cause.downcast_ref::<Failure>() {
+            return match failure {
+                Failure::Usage(_) => 2,
+                Failure::Parse(_) => 4,
+                Failure::Validation(_) => 5,
+            };
+        }
+        if cause.is::<InvalidSegments>() {
+            return 5;
+        }
+        if let Some(e) = cause.downcast_ref::<serde_json::Error>() {
+            return if e.is_io() { 3 } else { 4 };
+        }
+        if cause.is::<std::io::Error>() {
+            return 3;
+        }
+        if cause.is::<pico_args::Error>() {
+            return 2;
+        }
+        if cause.is::<toml::de::Error>()
+            || cause.is::<csv::Error>()
+            || cause.is::<quick_xml::Error>()
+        {
+            return 4;
+        }
+    }
+    1
+}
+
+const HELP: &str = "Usage: doukutsu-extractor [OPTIONS] COMMAND
 
 OPTIONS
   --translation_file FILE     Path to the JSON translation file (required).
+                              “dump” writes it to stdout if given “-”,
+                              leaving out any other output. Every command
+                              also takes a folder written by --split.
+  --split DIRECTORY           Make “dump” write the translation to this
+                              folder instead of --translation_file, split
+                              into one JSON file per script (e.g.
+                              Stage/Pens1.tsc.json) and an index.json, so
+                              that translators can each take some scripts
+                              without conflicts. Give the folder as the
+                              --translation_file of the other commands.
   --game_data DIRECTORY       Path to the game-data folder (required for
-                              the “dump” command).
+                              the “dump” command), to a single script, or
+                              to a zip archive holding the scripts, such as
+                              a downloaded mod.
   --output_dir DIRECTORY      Path to the output folder (required for the
-                              “write” command).
+                              “write” command). “dump” writes a transcript
+                              of each script there, like those of
+                              “export-script”, at the script's path plus
+                              .txt (e.g. Stage/Pens1.tsc.txt).
+  --base_translation FILE     Translation file that “write” takes the
+                              translation of each speech from that isn't
+                              translated at all in --translation_file, e.g.
+                              a complete English one under a partial
+                              Portuguese one. Speeches are matched by id, or
+                              by position if they have none.
+  --po_file FILE              Path to the gettext PO file (required for the
+                              “export-po” and “import-po” commands).
+  --csv_file FILE             Path to the CSV spreadsheet (required for the
+                              “export-csv” and “import-csv” commands).
+  --xliff_file FILE           Path to the XLIFF 2.0 file (required for the
+                              “export-xliff” and “import-xliff” commands).
+  --script_file FILE          Path to the transcript written by
+                              “export-script”.
+  --charset FILE              Text file containing every glyph the game font
+                              can render (required for “validate-glyphs”).
+                              “write” refuses to write translations using
+                              any other, as well as those the encoding
+                              can't represent, which it always checks.
+  --checkpoint FILE           Make “status” also write the progress it shows
+                              to this JSON file, listing the speeches marked
+                              done in each script, and “translate” keep it
+                              up to date as speeches are translated.
+  --encoding ENCODING         Text encoding of the game scripts, either
+                              “utf-8” (default) or “shift-jis” for the
+                              original Japanese release. Only used by “dump”;
+                              “write” re-encodes with the dumped encoding.
+  --merge                     When dumping into an existing translation file,
+                              keep its translations for every speech that is
+                              still there, going by its id even if it moved.
+                              Not allowed when dumping to stdout (“-”).
+  --strict                    Make “dump” fail on a file that can't be
+                              rebuilt exactly, instead of just warning.
+  --jobs N                    Number of threads “dump” and “write” process
+                              files with (default: one per CPU core).
+  --pretty                    Indent the translation file written by “dump”
+                              or the import commands, making it easier to edit
+                              by hand. Files that are already indented stay
+                              that way when updated in place.
+  --names FILE                TOML file mapping faces to the speaker names
+                              “dump” writes, e.g. `BalrogSmile = \"Balrog\"`.
+                              See names.toml for the vanilla cast.
+                              “export-script” also renames the speakers with
+                              it.
+  --faces FILE                TOML file naming the faces added by a mod,
+                              e.g. `FAC0045 = \"CustomNpc\"`. Unlisted
+                              modded faces are named after their code.
+  --context FILE              TOML file of notes for the translators, which
+                              “dump” adds to the speeches as their context.
+                              Each table is a script, like
+                              [\"Stage/Pens1.tsc\"], noting either a whole
+                              event, as in `\"#0100\" = \"Balrog bursts in\"`,
+                              or one speech by its dialogue and speech
+                              index, as in `\"2:1\" = \"...\"`.
+  --quiet                     Don't print how much text “dump” extracted, or
+                              the progress bars of “dump” and “write”, which
+                              are also left out when the output isn't a
+                              terminal.
+  --inline-codes              Make “dump” keep the control codes within each
+                              speech, such as <WAI0050 and the closing <NOD,
+                              in its text, so translators can see the pauses
+                              and box clears. “write” keeps whatever codes
+                              the translation contains.
+  --nod-breaks                Make “dump” start the text after each <NOD
+                              with the line breaks right after it, so that
+                              every line break in a message box is a \\n in
+                              the text that translators can move or remove.
+  --escape-bytes              Make “dump” write the bytes of the text that
+                              aren't plain characters, like the game's own
+                              glyphs or control characters, as \\x{NN} (e.g.
+                              \\x{81}), which “write” turns back into them.
+  --original                  Make “export-script” show the original text
+                              instead of the translation.
+  --filter-character NAME     Make “dump” keep only the speeches of the given
+                              speaker, named by face (e.g. BalrogSmile) or by
+                              the name --names gives it (e.g. Balrog), or
+                              “narration” for the text shown without a face.
+  --sample N                  Make “dump” keep only the first N dialogues
+                              of the game, to preview the extraction with.
+                              The translation file is marked as a sample,
+                              which “write” refuses.
+  --kind KIND                 Make “dump” keep only “narration”, the text
+                              shown without a face, or only “speech”, what
+                              the characters say, rather than “all” of it
+                              (default). Combined with --filter-character,
+                              the two must agree.
+  --include-empty             Make “dump” keep the scripts without any
+                              dialogue as well, so that “write” rebuilds the
+                              whole data folder.
+  --report-unknown            Make “dump” list the characters the lexer
+                              didn't recognize in each file, with the lines
+                              they're on, to find unsupported commands.
+  --dedupe-report             Make “dump” list the lines that appear more
+                              than once, so they can be translated once.
+  --propagate                 Make the import commands copy each translation
+                              to the untranslated lines with the same
+                              original text.
+  --max-width N               Widest line of dialogue, in characters, that
+                              “lint” accepts (default 35).
+  --wrap N                    Re-flow translated text into lines of at most N
+                              characters on “write”.
+  --force                     Make “write” rewrite files that are already up
+                              to date.
+  --check                     Make “write” rebuild every file without saving
+                              anything, listing the files it would write and
+                              those it couldn't.
+  --only-changed              Make “write” leave out the files that come out
+                              the same as the original, since nothing in
+                              them is translated, so the output can be laid
+                              over the game's own files as a patch.
+  --layout LAYOUT             Only “dump” the dialogue scripts of a
+                              “freeware” or Cave Story+ (“plus”) data folder:
+                              the ones at the top level, like Head.tsc and
+                              Credit.tsc, and those in Stage/. Cave Story+
+                              keeps both under base/, which “write” then
+                              recreates in the output directory. Without
+                              it, every .tsc file in the folder is dumped.
+  --pattern GLOB              Make “dump” look for scripts matching this
+                              glob, relative to the game-data folder,
+                              instead of `**/*.tsc`, for mods that name
+                              them differently, e.g. `**/*.pxeve`. Takes
+                              precedence over --layout.
+  --max-depth N               Make “dump” only look for scripts up to N
+                              folders deep, where 1 is only those directly
+                              in the game-data folder and 2 includes
+                              Stage/.
+  --files PATTERN             Only “dump” the scripts matching this glob,
+                              relative to the game-data folder, e.g.
+                              `Stage/Pens*.tsc`. With --merge, the other
+                              files in the translation file are left as-is.
+  --untranslated              Make “stats” fail if any speech is still
+                              untranslated, e.g. to block merges in CI.
+  --threshold N               Number of untranslated speeches that
+                              “stats --untranslated” tolerates (default 0).
+  --manifest FILE             Make “write” list every output file, with its
+                              size and SHA-256 hash, in this JSON file.
+                              Files that were already up to date are
+                              listed too.
+  --backup                    Make “write” copy every file it overwrites to
+                              FILE.bak first, unless that backup already
+                              exists. Useful when writing into the game's
+                              own data folder.
+  --allow-inplace             Let “write” write into the game-data folder the
+                              translation was dumped from (or a folder inside
+                              it), overwriting the original scripts.
+  --normalize-case            Make “write” spell the vanilla script names the
+                              way the game looks them up (e.g. HEAD.TSC
+                              becomes Head.TSC), for case-sensitive
+                              filesystems.
+  --force-extension           Make “write” give every file a lowercase .tsc
+                              extension.
+  --warn-whitespace           Make “write” warn about translations whose
+                              leading or trailing whitespace differs from
+                              the original's, which shifts the text in-game.
+  -v, --verbose               Log every script as it is read, decoded and
+                              lexed. RUST_LOG works as well.
+  -h, --help                  Show this help.
+  -V, --version               Show the version of doukutsu-extractor.
+  --no-decrypt                Make “dump” read scripts that aren't encrypted,
+                              as shipped by some mods. “write” then leaves
+                              them unencrypted as well.
+  --key-index N               Make “dump” decrypt scripts with the byte at
+                              offset N as the key, instead of the one in the
+                              middle. “write” encrypts them the same way.
+  --key-byte B                Make “dump” decrypt scripts with the key B
+                              (decimal, or hex like 0x20) instead of the one
+                              stored in them. “write” encrypts them the same
+                              way.
 
 COMMANDS
-  dump                        Extract translatable text from the game data
+  dump (or extract)           Extract translatable text from the game data
+                              into the translation file.
+  write (or build)            Re-build the game files from the translation file
+                              and write them to the output directory. Several
+                              translations can be built at once by giving
+                              each its own --output_dir, in the same order.
+  export-po                   Export the translation file as a gettext PO
+                              file, one entry per line of dialogue.
+  import-po                   Read the translations from a PO file back into
+                              the translation file.
+  export-csv                  Export the translation file as a CSV
+                              spreadsheet, one row per line of dialogue.
+  import-csv                  Read the translations from a CSV spreadsheet
+                              back into the translation file.
+  export-xliff                Export the translation file as XLIFF 2.0 for
+                              CAT tools, one unit per speech, with control
+                              codes as placeholders.
+  import-xliff                Read the translations from an XLIFF file back
                               into the translation file.
-  write                       Re-build the game files from the translation file
-                              and write them to the output directory.
+  export-script               Write the dialogue out as a transcript to read
+                              through, one “Character: text” line per speech,
+                              or just the text for narration (Markdown if
+                              the file ends in .md).
+  lint                        Check the translation file for translated lines
+                              that are too wide for the message box (not
+                              counting control codes), for speeches
+                              with more than 3 lines between two <NOD or
+                              <CLR, and for translations that lost, gained
+                              or reordered control codes like <NOD or
+                              <FAC0005, or that translate a command's
+                              arguments, like the O38 of <SOU0O38.
+  stats                       Count how many speeches have been translated,
+                              listing the first few that haven't.
+  status                      Show how many speeches of each script, and of
+                              the whole translation, translators have
+                              marked as done with `\"done\": true`.
+  diff                        Compare two translation files, given as
+                              `--translation_file OLD --translation_file NEW`,
+                              printing every speech that changed.
+  merge-translations          Fill in the untranslated lines of the first
+                              --translation_file with those translated in
+                              the ones after it, e.g. by different
+                              translators, listing the lines they translated
+                              differently. The first translation of a line
+                              is kept.
+  verify                      Check that the scripts in --game_data (or the
+                              folder the translation was dumped from) are
+                              the same ones it was dumped from.
+  validate-glyphs             Check the translations for glyphs that the
+                              game font can't render.
+  translate                   Step through the untranslated speeches,
+                              showing each with the speeches around it and
+                              its context, and type in their translations,
+                              which are marked done and saved to the
+                              translation file as they're entered. In a terminal this takes
+                              over the screen, with the keys shown at the
+                              bottom; piped, it reads a line per segment,
+                              with “:f PATTERN”, “:p” and “:q” to jump to a
+                              script, go back and quit.
+  schema                      Print a JSON Schema of the translation file,
+                              for editors to validate and autocomplete it
+                              with.
 
 EXAMPLES
   doukutsu-extractor --translation_file texts.json --game_data ./CaveStory/data dump
-  doukutsu-extractor --translation_file texts.json --output_dir ./out write"
-    ))
+  doukutsu-extractor --translation_file texts.json --output_dir ./out write
+  doukutsu-extractor --translation_file texts.json --po_file texts.po export-po
+
+EXIT CODES
+  0  Success.
+  1  Any other error, such as a script that can't be rebuilt.
+  2  The command line is wrong.
+  3  A file couldn't be read or written.
+  4  A translation, PO, CSV, XLIFF or TOML file couldn't be parsed.
+  5  “lint”, “stats”, “verify”, “validate-glyphs” or “write” found
+     problems, or the segments of a translation file don't fit the
+     scripts' original text.";
+
+/// Fails with the usage, for a command line that doesn't make sense.
+fn help() -> Result<()> {
+    Err(usage(HELP))
 }
 
-fn main() -> Result<()> {
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+fn run() -> Result<()> {
     let mut pargs = pico_args::Arguments::from_env();
 
+    // RUST_LOG can narrow things down further, e.g. to `doukutsu_extractor=debug`
+    let mut logger = env_logger::Builder::new();
+    logger
+        .filter_level(LevelFilter::Warn)
+        .parse_default_env()
+        .format(|buf, record| {
+            let level = match record.level() {
+                Level::Error => "Error",
+                Level::Warn => "Warning",
+                Level::Info => "Info",
+                Level::Debug => "Debug",
+                Level::Trace => "Trace",
+            };
+            writeln!(buf, "{level}: {}", record.args())
+        });
+    if pargs.contains(["-v", "--verbose"]) {
+        logger.filter_level(LevelFilter::Debug);
+    }
+    logger.init();
+
+    // these take precedence over everything else on the command line
+    if pargs.contains(["-V", "--version"]) {
+        println!("doukutsu-extractor {}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
+    if pargs.contains(["-h", "--help"]) {
+        println!("{HELP}");
+        return Ok(());
+    }
+
+    let translation_files = pargs.values_from_os_str("--translation_file", parse_path)?;
+    let output_dirs = pargs.values_from_os_str("--output_dir", parse_path)?;
+    let key = match (
+        pargs.opt_value_from_str("--key-index")?,
+        pargs.opt_value_from_fn("--key-byte", parse_byte)?,
+    ) {
+        (Some(_), Some(_)) => {
+            return Err(usage(
+                "`--key-index` and `--key-byte` can't be used together",
+            ));
+        }
+        (Some(idx), None) => Some(CipherKey::Index(idx)),
+        (None, Some(key)) => Some(CipherKey::Byte(key)),
+        (None, None) => None,
+    };
     let args = AppArgs {
         game_data: pargs.opt_value_from_os_str("--game_data", parse_path)?,
-        translation_file: pargs.opt_value_from_os_str("--translation_file", parse_path)?,
-        output_dir: pargs.opt_value_from_os_str("--output_dir", parse_path)?,
+        translation_file: translation_files.first().cloned(),
+        translation_files,
+        output_dir: output_dirs.first().cloned(),
+        output_dirs,
+        split: pargs.opt_value_from_os_str("--split", parse_path)?,
+        base_translation: pargs.opt_value_from_os_str("--base_translation", parse_path)?,
+        po_file: pargs.opt_value_from_os_str("--po_file", parse_path)?,
+        csv_file: pargs.opt_value_from_os_str("--csv_file", parse_path)?,
+        xliff_file: pargs.opt_value_from_os_str("--xliff_file", parse_path)?,
+        script_file: pargs.opt_value_from_os_str("--script_file", parse_path)?,
+        charset: pargs.opt_value_from_os_str("--charset", parse_path)?,
+        checkpoint: pargs.opt_value_from_os_str("--checkpoint", parse_path)?,
+        encoding: pargs.opt_value_from_str("--encoding")?.unwrap_or_default(),
+        merge: pargs.contains("--merge"),
+        max_width: pargs.opt_value_from_str("--max-width")?.unwrap_or(35),
+        wrap: pargs.opt_value_from_str("--wrap")?,
+        strict: pargs.contains("--strict"),
+        jobs: pargs.opt_value_from_str("--jobs")?,
+        pretty: pargs.contains("--pretty"),
+        names: pargs.opt_value_from_os_str("--names", parse_path)?,
+        faces: pargs.opt_value_from_os_str("--faces", parse_path)?,
+        context: pargs.opt_value_from_os_str("--context", parse_path)?,
+        quiet: pargs.contains("--quiet"),
+        force: pargs.contains("--force"),
+        no_decrypt: pargs.contains("--no-decrypt"),
+        key,
+        files: pargs.opt_value_from_str("--files")?,
+        pattern: pargs.opt_value_from_str("--pattern")?,
+        max_depth: pargs.opt_value_from_str("--max-depth")?,
+        untranslated: pargs.contains("--untranslated"),
+        threshold: pargs.opt_value_from_str("--threshold")?.unwrap_or(0),
+        layout: pargs.opt_value_from_str("--layout")?,
+        backup: pargs.contains("--backup"),
+        normalize_case: pargs.contains("--normalize-case"),
+        force_extension: pargs.contains("--force-extension"),
+        warn_whitespace: pargs.contains("--warn-whitespace"),
+        dedupe_report: pargs.contains("--dedupe-report"),
+        propagate: pargs.contains("--propagate"),
+        manifest: pargs.opt_value_from_os_str("--manifest", parse_path)?,
+        inline_codes: pargs.contains("--inline-codes"),
+        nod_breaks: pargs.contains("--nod-breaks"),
+        escape_bytes: pargs.contains("--escape-bytes"),
+        check: pargs.contains("--check"),
+        report_unknown: pargs.contains("--report-unknown"),
+        original: pargs.contains("--original"),
+        include_empty: pargs.contains("--include-empty"),
+        only_changed: pargs.contains("--only-changed"),
+        allow_inplace: pargs.contains("--allow-inplace"),
+        filter_character: pargs.opt_value_from_str("--filter-character")?,
+        kind: pargs.opt_value_from_str("--kind")?.unwrap_or_default(),
+        sample: pargs.opt_value_from_str("--sample")?,
     };
 
+    let import_options = ImportOptions {
+        pretty: args.pretty,
+        propagate: args.propagate,
+    };
     let subcommand = pargs.subcommand();
 
     match subcommand {
         Ok(Some(sc)) => match sc.as_str() {
-            "dump" => dump(
+            "dump" | "extract" => dump(
                 args.game_data
-                    .ok_or(anyhow!("missing `--game_data DIRECTORY`"))?,
+                    .clone()
+                    .ok_or_else(|| usage("missing `--game_data DIRECTORY`"))?,
+                match (&args.translation_file, &args.split) {
+                    (Some(_), Some(_)) => {
+                        return Err(usage(
+                            "`--split` takes the place of `--translation_file`; give only one",
+                        ));
+                    }
+                    (Some(path), None) | (None, Some(path)) => path.clone(),
+                    (None, None) => {
+                        return Err(usage("missing --translation_file FILE.json"));
+                    }
+                },
+                &args,
+            ),
+            "write" | "build" => {
+                args.translation_file
+                    .as_ref()
+                    .ok_or_else(|| usage("missing --translation_file FILE.json"))?;
+                args.output_dir
+                    .as_ref()
+                    .ok_or_else(|| usage("missing --output_dir"))?;
+                if args.translation_files.len() != args.output_dirs.len() {
+                    return Err(usage(format!(
+                        "got {} translation files but {} output directories; pass one --output_dir per --translation_file",
+                        args.translation_files.len(),
+                        args.output_dirs.len()
+                    )));
+                }
+                let options = WriteOptions {
+                    wrap: args.wrap,
+                    force: args.force,
+                    backup: args.backup,
+                    normalize_case: args.normalize_case,
+                    force_extension: args.force_extension,
+                    check: args.check,
+                    progress: args.progress(),
+                    only_changed: args.only_changed,
+                    jobs: args.jobs,
+                };
+                let charset = match &args.charset {
+                    Some(path) => Some(read_charset(path)?),
+                    None => None,
+                };
+                let base = match &args.base_translation {
+                    Some(path) => Some(translated_segments(&DialogueData::read(path)?)),
+                    None => None,
+                };
+                // each translation is built into the output directory given
+                // in the same position
+                let mut summaries = vec![];
+                for (translation_file, output_dir) in
+                    args.translation_files.iter().zip(&args.output_dirs)
+                {
+                    summaries.push(write(
+                        translation_file.clone(),
+                        output_dir.to_path_buf(),
+                        &options,
+                        args.warn_whitespace,
+                        args.allow_inplace,
+                        base.as_ref(),
+                        charset.as_ref(),
+                    )?);
+                }
+                // the manifest hashes the files on disk, which a check
+                // doesn't produce
+                if let Some(manifest) = args.manifest.as_ref().filter(|_| !args.check) {
+                    write_manifest(manifest, &summaries)?;
+                }
+                write_failures(&summaries)
+            }
+            "export-po" => export_po(
+                args.translation_file
+                    .ok_or_else(|| usage("missing --translation_file FILE.json"))?,
+                args.po_file
+                    .ok_or_else(|| usage("missing --po_file FILE.po"))?,
+            ),
+            "import-po" => import_po(
+                args.translation_file
+                    .ok_or_else(|| usage("missing --translation_file FILE.json"))?,
+                args.po_file
+                    .ok_or_else(|| usage("missing --po_file FILE.po"))?,
+                &import_options,
+            ),
+            "export-csv" => export_csv(
                 args.translation_file
-                    .ok_or(anyhow!("missing --translation_file FILE.json"))?,
+                    .ok_or_else(|| usage("missing --translation_file FILE.json"))?,
+                args.csv_file
+                    .ok_or_else(|| usage("missing --csv_file FILE.csv"))?,
             ),
-            "write" => write(
+            "import-csv" => import_csv(
                 args.translation_file
-                    .ok_or(anyhow!("missing --translation_file FILE.json"))?,
-                args.output_dir.ok_or(anyhow!("missing --output_dir"))?,
+                    .ok_or_else(|| usage("missing --translation_file FILE.json"))?,
+                args.csv_file
+                    .ok_or_else(|| usage("missing --csv_file FILE.csv"))?,
+                &import_options,
             ),
+            "lint" => lint(
+                args.translation_file
+                    .ok_or_else(|| usage("missing --translation_file FILE.json"))?,
+                args.max_width,
+            ),
+            "stats" => stats(
+                args.translation_file
+                    .ok_or_else(|| usage("missing --translation_file FILE.json"))?,
+                args.untranslated,
+                args.threshold,
+            ),
+            "export-script" => export_script(
+                args.translation_file
+                    .ok_or_else(|| usage("missing --translation_file FILE.json"))?,
+                args.script_file
+                    .ok_or_else(|| usage("missing --script_file FILE.txt"))?,
+                args.original,
+                &match &args.names {
+                    Some(path) => toml::from_str(&std::fs::read_to_string(path)?)?,
+                    None => BTreeMap::new(),
+                },
+            ),
+            "export-xliff" => export_xliff(
+                args.translation_file
+                    .ok_or_else(|| usage("missing --translation_file FILE.json"))?,
+                args.xliff_file
+                    .ok_or_else(|| usage("missing --xliff_file FILE.xlf"))?,
+            ),
+            "import-xliff" => import_xliff(
+                args.translation_file
+                    .ok_or_else(|| usage("missing --translation_file FILE.json"))?,
+                args.xliff_file
+                    .ok_or_else(|| usage("missing --xliff_file FILE.xlf"))?,
+                &import_options,
+            ),
+            "diff" => match args.translation_files.as_slice() {
+                [old, new] => diff(old.clone(), new.clone()),
+                _ => Err(usage(
+                    "diff needs two translation files: `--translation_file OLD.json --translation_file NEW.json`",
+                )),
+            },
+            "merge-translations" => match args.translation_files.as_slice() {
+                [base, contributions @ ..] if !contributions.is_empty() => {
+                    merge_files(base.clone(), contributions, &import_options)
+                }
+                _ => Err(usage(
+                    "merge-translations needs the base file and at least one contribution: `--translation_file BASE.json --translation_file A.json --translation_file B.json`",
+                )),
+            },
+            "verify" => verify(
+                args.translation_file
+                    .ok_or_else(|| usage("missing --translation_file FILE.json"))?,
+                args.game_data,
+            ),
+            "validate-glyphs" => validate_glyphs(
+                args.translation_file
+                    .ok_or_else(|| usage("missing --translation_file FILE.json"))?,
+                args.charset
+                    .ok_or_else(|| usage("missing --charset FILE"))?,
+            ),
+            "status" => status(
+                args.translation_file
+                    .ok_or_else(|| usage("missing --translation_file FILE.json"))?,
+                args.checkpoint,
+            ),
+            "translate" => translate(
+                args.translation_file
+                    .ok_or_else(|| usage("missing --translation_file FILE.json"))?,
+                args.pretty,
+                args.checkpoint,
+            ),
+            "schema" => schema(),
+            "help" => {
+                println!("{HELP}");
+                Ok(())
+            }
             _ => help(),
         },
         _ => help(),
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use doukutsu_extractor::dump_to_data;
+    use std::path::Path;
+
+    /// Dumps `script` as `Test.tsc` into a translation file in `dir`, letting
+    /// `edit` change it first.
+    fn translation(dir: &Path, script: &[u8], edit: impl FnOnce(&mut DialogueData)) -> PathBuf {
+        translation_with(dir, script, DumpOptions::default(), edit)
+    }
+
+    /// [`translation`] dumped with `options`.
+    fn translation_with(
+        dir: &Path,
+        script: &[u8],
+        options: DumpOptions,
+        edit: impl FnOnce(&mut DialogueData),
+    ) -> PathBuf {
+        let options = DumpOptions {
+            plaintext: true,
+            ..options
+        };
+        let data = dir.join("data");
+        std::fs::create_dir_all(&data).unwrap();
+        std::fs::write(data.join("Test.tsc"), script).unwrap();
+        let mut dd = dump_to_data(data, &options).unwrap();
+        edit(&mut dd);
+        let path = dir.join("texts.json");
+        dd.save(&path, false).unwrap();
+        path
+    }
+
+    /// Text with everything a PO or CSV file has to quote.
+    const AWKWARD: &str = "Il a dit \"non\", puis\\rien.\nVraiment\\n rien.";
+
+    #[test]
+    fn po_files_round_trip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let script = b"#0100\r\n<MSGHello.<NOD<END\r\n#0200\r\n<MSGBye.<NOD<END\r\n";
+        let path = translation(dir, script, |dd| {
+            dd.files[0].dialogues[0][0].text[0].0 = AWKWARD.to_string();
+            dd.files[0].dialogues[1][0].text[0].0 = "Au revoir.".to_string();
+        });
+        let po_file = dir.join("texts.po");
+        export_po(path, po_file.clone()).unwrap();
+        // editors wrap long strings over several lines
+        let po = std::fs::read_to_string(&po_file).unwrap();
+        let wrapped = po.replacen(
+            "msgstr \"Au revoir.\"",
+            "msgstr \"\"\n\"Au \"\n\"revoir.\"",
+            1,
+        );
+        assert_ne!(wrapped, po);
+        std::fs::write(&po_file, wrapped).unwrap();
+        let path = translation(dir, script, |_| {});
+        let options = ImportOptions {
+            pretty: false,
+            propagate: false,
+        };
+        import_po(path.clone(), po_file, &options).unwrap();
+        let dd = DialogueData::read(&path).unwrap();
+        assert_eq!(dd.files[0].dialogues[0][0].text[0].0, AWKWARD);
+        assert_eq!(dd.files[0].dialogues[1][0].text[0].0, "Au revoir.");
+    }
+
+    #[test]
+    fn csv_files_round_trip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let script = b"#0100\r\n<MSGHello, you.<NOD<END\r\n#0200\r\n<MSGBye.<NOD<END\r\n";
+        let path = translation(dir, script, |dd| {
+            dd.files[0].dialogues[0][0].text[0].0 = AWKWARD.to_string();
+        });
+        let csv_file = dir.join("texts.csv");
+        export_csv(path, csv_file.clone()).unwrap();
+        let path = translation(dir, script, |_| {});
+        let options = ImportOptions {
+            pretty: false,
+            propagate: false,
+        };
+        import_csv(path.clone(), csv_file, &options).unwrap();
+        let dd = DialogueData::read(&path).unwrap();
+        assert_eq!(dd.files[0].dialogues[0][0].text[0].0, AWKWARD);
+        assert_eq!(dd.files[0].dialogues[1][0].text[0].0, "Bye.");
+    }
 
-    // let reconstructed = files[2].reconstruct();
-    // println!("PARSED: {:#?}", files[2].dialogues);
-    // println!("RECONSTRUCTED:\n {:#?}", reconstructed);
-    // println!("ORIGINAL:\n {:#?}", files[2].original)
+    #[test]
+    fn lint_only_measures_translated_lines() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let script =
+            b"#0100\r\n<MSGThis original line is too wide.<NOD<WAI0050And so is this one.<NOD<END\r\n";
+        let options = DumpOptions {
+            inline_codes: true,
+            ..Default::default()
+        };
+        let path = translation_with(dir, script, options, |_| {});
+        let mut dd = DialogueData::read(&path).unwrap();
+        lint(path.clone(), 20).unwrap();
+        for (translated, fits) in [
+            ("Short.<NOD<WAI0050Short again.<NOD", true),
+            ("Short.\nShort too.<NOD<WAI0050Short again.<NOD", true),
+            (
+                "Short.<NOD<WAI0050But this one goes on too long.<NOD",
+                false,
+            ),
+            ("A translation still too wide.<NOD<WAI0050Short.<NOD", false),
+        ] {
+            dd.files[0].dialogues[0][0].text[0].0 = translated.to_string();
+            dd.save(&path, false).unwrap();
+            match lint(path.clone(), 20) {
+                Ok(()) => assert!(fits, "{translated:?} should be too wide"),
+                Err(e) => assert!(!fits && exit_code(&e) == 5, "{translated:?}: {e}"),
+            }
+        }
+    }
+
+    #[test]
+    fn xliff_keeps_the_whitespace_of_the_text() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let script = b"#0100\r\n<MSGHello.<NOD<CLRGoodbye.<NOD<END\r\n";
+        let translated = "  Salut,\n   toi.  ";
+        let path = translation(dir, script, |dd| {
+            dd.files[0].dialogues[0][0].text[0].0 = translated.to_string();
+        });
+        let xliff_file = dir.join("texts.xlf");
+        export_xliff(path.clone(), xliff_file.clone()).unwrap();
+        let xliff = std::fs::read_to_string(&xliff_file).unwrap();
+        assert!(xliff.contains(&format!(
+            "<target xml:space=\"preserve\">{translated}</target>"
+        )));
+        assert!(xliff.contains("<source xml:space=\"preserve\">Goodbye.</source>"));
+        let path = translation(dir, script, |_| {});
+        let options = ImportOptions {
+            pretty: false,
+            propagate: false,
+        };
+        import_xliff(path.clone(), xliff_file, &options).unwrap();
+        let dd = DialogueData::read(&path).unwrap();
+        assert_eq!(dd.files[0].dialogues[0][0].text[0].0, translated);
+    }
+
+    #[test]
+    fn dumping_to_stdout_refuses_to_merge() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::write(dir.join("Test.tsc"), b"#0100\r\n<MSGHello.<NOD<END\r\n").unwrap();
+        let args = AppArgs {
+            merge: true,
+            no_decrypt: true,
+            ..Default::default()
+        };
+        let e = dump(dir.to_path_buf(), PathBuf::from("-"), &args).unwrap_err();
+        assert_eq!(exit_code(&e), 2, "{e}");
+    }
+
+    #[test]
+    fn writing_a_script_dumped_by_a_relative_path() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        // as `dump --game_data Stage/Pens1.tsc` records it
+        let path = translation(dir, b"#0100\r\n<MSGHello.<NOD<END\r\n", |dd| {
+            dd.game_data_root = PathBuf::new();
+        });
+        let write = |output_dir: PathBuf| {
+            let options = WriteOptions::default();
+            write(path.clone(), output_dir, &options, false, false, None, None)
+        };
+        let summary = write(dir.join("out")).unwrap();
+        assert_eq!(summary.written, [dir.join("out/Test.tsc")]);
+        // which is the current folder
+        let e = write(PathBuf::from("out")).unwrap_err();
+        assert_eq!(exit_code(&e), 2, "{e}");
+    }
+
+    #[test]
+    fn status_checkpoints_the_speeches_marked_done() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let script = b"#0100\r\n<MSGHello.<NOD<END\r\n#0200\r\n<MSGBye.<NOD<END\r\n";
+        let mut id = None;
+        let path = translation(dir, script, |dd| {
+            let speech = &mut dd.files[0].dialogues[1][0];
+            speech.done = true;
+            id = speech.id.clone();
+        });
+        let checkpoint = dir.join("progress.json");
+        status(path, Some(checkpoint.clone())).unwrap();
+        let progress = std::fs::read_to_string(checkpoint).unwrap();
+        let progress: serde_json::Value = serde_json::from_str(&progress).unwrap();
+        assert_eq!(
+            (&progress["done"], &progress["total"]),
+            (&1.into(), &2.into())
+        );
+        let file = &progress["files"]["Test.tsc"];
+        assert_eq!(file["done_speeches"], serde_json::json!([id.unwrap()]));
+    }
+
+    #[test]
+    fn files_that_cant_be_written_exit_with_an_io_failure() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let script = b"#0100\r\n<MSGHello.<NOD<END\r\n";
+        let write = |path: PathBuf| {
+            let options = WriteOptions::default();
+            let summary = write(path, dir.join("out"), &options, false, false, None, None).unwrap();
+            assert_eq!(summary.failed.len(), 1);
+            write_failures(&[summary]).unwrap_err()
+        };
+        // a folder where the script should go
+        std::fs::create_dir_all(dir.join("out/Test.tsc")).unwrap();
+        let e = write(translation(dir, script, |_| {}));
+        assert_eq!(exit_code(&e), 3, "{e}");
+        let e = write(translation(dir, script, |dd| {
+            dd.files[0].path = PathBuf::from("../Test.tsc");
+        }));
+        assert_eq!(exit_code(&e), 5, "{e}");
+    }
+
+    #[test]
+    fn write_with_a_charset_refuses_invalid_segments() {
+        let charset: HashSet<char> = ('\0'..='\u{ff}').collect();
+        for (name, start, end) in [("past-end", 0, 100), ("mid-char", 4, 0)] {
+            let tmp = tempfile::tempdir().unwrap();
+            let dir = tmp.path();
+            let path = translation(dir, "#0100\r\n<MSGCafé?<NOD<END\r\n".as_bytes(), |dd| {
+                let range = &mut dd.files[0].dialogues[0][0].text[0].1;
+                range.start += start;
+                range.end += end;
+            });
+            let output_dir = dir.join("out");
+            let e = write(
+                path,
+                output_dir.to_path_buf(),
+                &WriteOptions::default(),
+                false,
+                false,
+                None,
+                Some(&charset),
+            )
+            .unwrap_err();
+            assert_eq!(exit_code(&e), 5, "{name}: {e}");
+            assert!(!output_dir.exists(), "{name}: nothing should be written");
+        }
+    }
+
+    #[test]
+    fn the_help_shows_escapes_as_they_are_typed() {
+        assert!(HELP.contains(r"is a \n in"));
+        assert!(HELP.contains(r"as \x{NN}"));
+    }
+
+    #[test]
+    fn importing_keeps_the_formatting_of_the_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let script = b"#0100\r\n<MSGHello.<NOD<END\r\n";
+        let path = translation(dir, script, |dd| {
+            dd.files[0].dialogues[0][0].text[0].0 = "Salut.".to_string();
+        });
+        let xliff_file = dir.join("texts.xlf");
+        export_xliff(path.clone(), xliff_file.clone()).unwrap();
+        for pretty in [false, true] {
+            let path = translation(dir, script, |_| {});
+            DialogueData::read(&path)
+                .unwrap()
+                .save(&path, pretty)
+                .unwrap();
+            let options = ImportOptions {
+                pretty: false,
+                propagate: false,
+            };
+            import_xliff(path.clone(), xliff_file.clone(), &options).unwrap();
+            assert_eq!(DialogueData::is_pretty(&path), pretty);
+        }
+    }
+
+    #[test]
+    fn the_translate_screen_saves_each_speech() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let script = b"#0100\r\n<MSGHello.<NOD<END\r\n#0200\r\n<MSGBye.<NOD<END\r\n";
+        let path = translation(dir, script, |_| {});
+        let dd = DialogueData::read(&path).unwrap();
+        let queue = untranslated_speeches(&dd);
+        assert_eq!(queue.len(), 2);
+        let output = TranslateOutput {
+            translation_file: path.clone(),
+            pretty: false,
+            checkpoint: Some(dir.join("progress.json")),
+        };
+        let mut translator = Translator::new(dd, queue, output);
+        let mut press = |code: KeyCode, modifiers: KeyModifiers| {
+            translator.handle(KeyEvent::new(code, modifiers)).unwrap()
+        };
+        let key = KeyModifiers::NONE;
+        for c in "Bonjr".chars() {
+            press(KeyCode::Char(c), key);
+        }
+        press(KeyCode::Left, key);
+        for c in "ou".chars() {
+            press(KeyCode::Char(c), key);
+        }
+        press(KeyCode::Enter, key);
+        let speech = |d: usize| DialogueData::read(&path).unwrap().files[0].dialogues[d][0].clone();
+        assert_eq!(speech(0).text[0].0, "Bonjour");
+        assert!(speech(0).done);
+        let progress = std::fs::read_to_string(dir.join("progress.json")).unwrap();
+        let progress: serde_json::Value = serde_json::from_str(&progress).unwrap();
+        assert_eq!(progress["done"], 1);
+        // skipping a speech leaves it as it is
+        press(KeyCode::Enter, key);
+        assert_eq!(speech(1).text[0].0, "Bye.");
+        assert!(!speech(1).done);
+        press(KeyCode::Char('p'), KeyModifiers::CONTROL);
+        press(KeyCode::Char('f'), KeyModifiers::CONTROL);
+        for c in "Stage/*".chars() {
+            press(KeyCode::Char(c), key);
+        }
+        press(KeyCode::Enter, key);
+        assert!(translator.message.contains("No untranslated speech"));
+        assert_eq!(translator.prompt, Prompt::Segment);
+        assert_eq!(
+            (translator.i, &translator.drafts[..]),
+            (0, &["Bonjour".to_string()][..])
+        );
+        let (lines, _) = translator.screen(30);
+        assert!(lines.iter().all(|line| line.content().width() <= 30));
+        assert!(!translator.handle(KeyEvent::from(KeyCode::Esc)).unwrap());
+    }
 }
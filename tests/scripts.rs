@@ -0,0 +1,279 @@
+//! Extraction tests over the sample scripts in `tests/fixtures`, which are
+//! kept decrypted so that they can be read and edited. They're encrypted the
+//! way the game stores them before being dumped.
+
+use std::path::{Path, PathBuf};
+
+use doukutsu_extractor::*;
+
+fn fixture_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+/// The decrypted contents of the fixture `name`.
+fn fixture(name: &str) -> Vec<u8> {
+    let path = fixture_dir().join(name);
+    std::fs::read(&path).unwrap_or_else(|e| panic!("{path:?}: {e}"))
+}
+
+/// Encrypts `script` and dumps it as `name`, failing if it doesn't
+/// reconstruct to what it was.
+fn dump(name: &str, script: &[u8]) -> FileData {
+    dump_with(name, script, DumpOptions::default())
+}
+
+/// [`dump`] with `options`.
+fn dump_with(name: &str, script: &[u8], options: DumpOptions) -> FileData {
+    let options = DumpOptions {
+        strict: true,
+        ..options
+    };
+    let raw = tsc_encode(script.to_vec(), None).unwrap();
+    dump_script(PathBuf::from(name), raw, &options)
+        .unwrap()
+        .unwrap_or_else(|| panic!("{name} has no dialogue"))
+}
+
+/// The text of every speech in `fd`, by dialogue.
+fn texts(fd: &FileData) -> Vec<Vec<String>> {
+    fd.dialogues
+        .iter()
+        .map(|dialogue| {
+            dialogue
+                .iter()
+                .map(|speech| speech.text.iter().map(|(text, _)| text.as_str()).collect())
+                .collect()
+        })
+        .collect()
+}
+
+/// The speaker of every speech in `fd`, by dialogue.
+fn speakers(fd: &FileData) -> Vec<Vec<&str>> {
+    fd.dialogues
+        .iter()
+        .map(|dialogue| dialogue.iter().map(Speech::speaker).collect())
+        .collect()
+}
+
+#[test]
+fn item_ids_stay_out_of_the_text() {
+    let fd = dump("ArmsItem.tsc", &fixture("ArmsItem.tsc"));
+    assert_eq!(texts(&fd)[0], ["Got the =Missile Launcher=!"]);
+}
+
+#[test]
+fn flow_codes_stay_out_of_the_text() {
+    let fd = dump("Stage/Pens1.tsc", &fixture("Pens1.tsc"));
+    assert_eq!(
+        texts(&fd),
+        [
+            vec!["Well... I guess\nso.", "Don't wander off."],
+            vec!["The door is locked."]
+        ]
+    );
+}
+
+#[test]
+fn each_end_closes_a_dialogue() {
+    let fd = dump("Stage/Egg1.tsc", &fixture("Egg1.tsc"));
+    assert_eq!(
+        texts(&fd),
+        [
+            vec!["It's a computer.", "Nothing on the screen."],
+            vec!["The door won't open."],
+            vec!["Let me through!"],
+        ]
+    );
+    let events: Vec<_> = fd.dialogues.iter().map(|d| d[0].event).collect();
+    assert_eq!(events, [Some(200), Some(201), Some(202)]);
+}
+
+#[test]
+fn transport_arguments_stay_out_of_the_text() {
+    let fd = dump("Stage/Teleporter.tsc", &fixture("Teleporter.tsc"));
+    assert_eq!(
+        texts(&fd),
+        [vec!["Off to the Egg Corridor."], vec!["You made it."]]
+    );
+    // the face doesn't carry over to the map it goes to
+    assert!(fd.dialogues[0][0].character.is_some());
+    assert_eq!(fd.dialogues[1][0].character, None);
+}
+
+#[test]
+fn numbers_can_be_moved_within_the_text() {
+    let script = fixture("ArmsItem.tsc");
+    let mut fd = dump("ArmsItem.tsc", &script);
+    let speech = &mut fd.dialogues[2][0];
+    assert_eq!(speech.text.len(), 1);
+    assert_eq!(speech.text[0].0, "Got the <NUM0000 =Life Pot=!");
+    speech.text[0].0 = "=Life Pot= x<NUM0000 obtained!".to_string();
+    let rebuilt = fd.reconstruct_bytes(TextEncoding::Utf8, None).unwrap();
+    let expected = String::from_utf8(script).unwrap().replace(
+        "Got the <NUM0000 =Life Pot=!",
+        "=Life Pot= x<NUM0000 obtained!",
+    );
+    assert_eq!(rebuilt, expected.as_bytes());
+}
+
+#[test]
+fn music_ids_stay_out_of_the_text() {
+    let fd = dump("Stage/Cutscene.tsc", &fixture("Cutscene.tsc"));
+    assert_eq!(
+        texts(&fd)[..2],
+        [vec!["Huzzah!", "You'll never catch me!"], vec!["Silence."]]
+    );
+}
+
+#[test]
+fn the_face_stays_up_across_boxes() {
+    let fd = dump("Faces.tsc", &fixture("Faces.tsc"));
+    let speakers = speakers(&fd);
+    let balrog = speakers[0][0];
+    assert_ne!(balrog, "Narration");
+    assert_eq!(
+        speakers[..3],
+        [vec![balrog, balrog], vec![balrog], vec![balrog]]
+    );
+    assert_eq!(speakers.last().unwrap(), &["Narration"]);
+}
+
+#[test]
+fn credit_lines_are_extracted_by_section() {
+    let script = fixture("Credit.tsc");
+    let mut fd = dump("Credit.tsc", &script);
+    assert_eq!(
+        texts(&fd),
+        [
+            vec!["STAFF"],
+            vec!["Cave Story", "Studio Pixel"],
+            vec!["Producer", "Daisuke Amaya"],
+        ]
+    );
+    let events: Vec<_> = fd.dialogues.iter().map(|d| d[0].event).collect();
+    assert_eq!(events, [None, Some(100), Some(200)]);
+    fd.dialogues[2][0].text[0].0 = "Produzent".to_string();
+    let rebuilt = fd.reconstruct_bytes(TextEncoding::Utf8, None).unwrap();
+    let expected = String::from_utf8(script)
+        .unwrap()
+        .replace("[Producer]", "[Produzent]");
+    assert_eq!(rebuilt, expected.as_bytes());
+}
+
+#[test]
+fn stat_amounts_stay_out_of_the_text() {
+    let fd = dump("ArmsItem.tsc", &fixture("ArmsItem.tsc"));
+    let texts = texts(&fd);
+    assert_eq!(texts[0], ["Got the =Missile Launcher=!"]);
+    assert_eq!(texts[1], ["Max health increased by 5!"]);
+    assert_eq!(texts[3], ["You feel better."]);
+}
+
+#[test]
+fn lf_scripts_extract_like_crlf_ones() {
+    for entry in std::fs::read_dir(fixture_dir()).unwrap() {
+        let name = entry.unwrap().file_name().into_string().unwrap();
+        let crlf = fixture(&name);
+        let lf = String::from_utf8(crlf.clone())
+            .unwrap()
+            .replace("\r\n", "\n");
+        let (crlf, lf) = (dump(&name, &crlf), dump(&name, lf.as_bytes()));
+        assert_eq!(texts(&lf), texts(&crlf), "{name}");
+        assert_eq!(speakers(&lf), speakers(&crlf), "{name}");
+        assert!(
+            !lf.reconstruct_bytes(TextEncoding::Utf8, None)
+                .unwrap()
+                .contains(&b'\r')
+        );
+    }
+}
+
+#[test]
+fn each_box_style_opens_a_dialogue() {
+    let fd = dump("Boxes.tsc", &fixture("Boxes.tsc"));
+    assert_eq!(
+        texts(&fd),
+        [
+            vec!["Bottom box."],
+            vec!["Invisible one."],
+            vec!["Top box."],
+            vec!["Second top.", "Still top."],
+        ]
+    );
+    let styles: Vec<Vec<_>> = fd
+        .dialogues
+        .iter()
+        .map(|d| d.iter().map(|s| s.box_style).collect())
+        .collect();
+    use BoxStyle::*;
+    assert_eq!(
+        styles,
+        [vec![Bottom], vec![Invisible], vec![Top], vec![Top, Top]]
+    );
+    // the face stays up in the new box, until it's taken down
+    let speakers = speakers(&fd);
+    assert_eq!(speakers[1], speakers[0]);
+    assert_eq!(speakers[2], ["Narration"]);
+}
+
+#[test]
+fn faces_put_up_without_text_make_no_speech() {
+    let fd = dump("Faces.tsc", &fixture("Faces.tsc"));
+    let (texts, speakers) = (texts(&fd), speakers(&fd));
+    // a face that's changed again before anything is said
+    assert_eq!(texts[3], ["Hello.", "World."]);
+    assert_ne!(speakers[3][0], speakers[3][1]);
+    assert_eq!(texts[4], ["Both faces.", "Narrated."]);
+    assert_eq!(speakers[4][1], "Narration");
+}
+
+#[test]
+fn untranslated_samples_are_written_back_unchanged() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    let data = dir.join("data");
+    std::fs::create_dir_all(&data).unwrap();
+    let names: Vec<_> = std::fs::read_dir(fixture_dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    for name in &names {
+        let script = fixture(name.to_str().unwrap());
+        std::fs::write(data.join(name), tsc_encode(script, None).unwrap()).unwrap();
+    }
+    let options = DumpOptions {
+        strict: true,
+        ..Default::default()
+    };
+    let dd = dump_to_data(data.clone(), &options).unwrap();
+    assert_eq!(dd.files.len(), names.len());
+    let out = dir.join("out");
+    let summary = write_from_data(&dd, &out, &WriteOptions::default()).unwrap();
+    assert_eq!(summary.written.len(), names.len());
+    for name in &names {
+        let written = std::fs::read(out.join(name)).unwrap();
+        assert_eq!(written, std::fs::read(data.join(name)).unwrap(), "{name:?}");
+    }
+}
+
+#[test]
+fn locking_and_freeing_the_player_ends_the_speech() {
+    let script = fixture("Cutscene.tsc");
+    let fd = dump("Stage/Cutscene.tsc", &script);
+    assert_eq!(
+        texts(&fd)[2],
+        ["Wait right there!", "Don't move.", "...whew."]
+    );
+    let speakers = speakers(&fd);
+    assert_eq!(speakers[2][0], speakers[2][1]);
+    assert_eq!(speakers[2][2], "Narration");
+    let options = DumpOptions {
+        inline_codes: true,
+        ..Default::default()
+    };
+    let fd = dump_with("Stage/Cutscene.tsc", &script, options);
+    assert_eq!(
+        texts(&fd)[2],
+        ["Wait right there!<NOD", "Don't move.<NOD", "...whew.<NOD"]
+    );
+}
@@ -22,9 +22,14 @@ CONTEXT = (
 )
 
 
+def speaker(speech):
+    # narration has no character; the prompts call it 'NP'
+    return speech["character"] or "NP"
+
+
 def data_summary(data):
     dialogues = data["dialogues"]
-    return [{s["character"]: t[0]} for d in dialogues for s in d for t in s["text"]]
+    return [{speaker(s): t[0]} for d in dialogues for s in d for t in s["text"]]
 
 
 def json_parse(s):
@@ -93,7 +98,7 @@ def translate_dialogue(summary, previous_dialogues, dialogue):
         f"   - Do not break words in half unless necessary (if necessary, use a hyphen).\n"
         f"   - There must be exactly the same number of speeches as in the English. ({len(dialogue)})\n"
         f"   - Each speech list must be broken up exactly like the English, and must contain exactly the same number of items as in the English. ({json.dumps(pattern)})\n"
-        f"   - The line breaks within each item, however, may be adjusted (but must still use `\\r\\n` every time).\n"
+        f"   - The line breaks within each item, however, may be adjusted (but must still use `\\n` every time).\n"
         f"   - Count your segmented version before outputting to ensure that it fits the constraints.\n"
     )
 
@@ -105,9 +110,9 @@ def translate_dialogue(summary, previous_dialogues, dialogue):
         f"### Style Guide\n"
         f"{json.dumps(summary['style_guide'])}\n\n"
         f"### Example Input\n"
-        f'[{{"Char1": ["I see.\r\nI can\'t do this myself.", "Can you?"]}}, {{"Char2": ["Yes."]}}]\n\n'
+        f'[{{"Char1": ["I see.\\nI can\'t do this myself.", "Can you?"]}}, {{"Char2": ["Yes."]}}]\n\n'
         f"### Example Output\n"
-        f'[["Video.\r\Hoc facere solus nequeo.", "Potesne?"], ["Possum."]]\n'
+        f'[["Video.\\nHoc facere solus nequeo.", "Potesne?"], ["Possum."]]\n'
         f"### Instructions\n"
         f"1. **Check Context**: Look at the Preceding Dialogues and determine if they provide relevant context or are unrelated. Be wary that, due to the structure of the dialogue files, dialogues may be only coincidentally adjacent.\n"
         f"2. **Analyze**: Determine the grammatical structure (Subject, Object, Verb) of the Dialogue to Translate.\n"
@@ -163,7 +168,7 @@ def translate_dialogue(summary, previous_dialogues, dialogue):
     return (parsed, cost)
 
 def format_dialogue(dl):
-    return [{s['character']: [t[0] for t in s['text']]} for s in dl]
+    return [{speaker(s): [t[0] for t in s['text']]} for s in dl]
 
 def make_translation(input: Path, output: Path):
     cost = 0.0